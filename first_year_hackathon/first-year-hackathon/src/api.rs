@@ -0,0 +1,455 @@
+//! versioned json api for scripting notes from outside the browser
+//!
+//! every request needs an `Authorization: Bearer <token>` header, tokens are
+//! created and revoked from the settings page and act as the user who made them.
+//! if the server sets `session_lifetime_days`, tokens left unused that long expire.
+//!
+//! - `GET /v1/me` returns the user the token belongs to
+//! - `GET /v1/notes` lists notes the caller can see, filtered by the optional
+//!   `pinned`, `tag`, `q` (title/body search) and `overdue` query parameters
+//! - `POST /v1/notes` creates a note from `{ title, body?, tags?, pinned?, due? }`
+//! - `GET /v1/notes/{id}` fetches a single note
+//! - `PATCH /v1/notes/{id}` updates any of `title`, `body`, `tags`, `pinned`, `due`
+//!   (`"due": null` clears it), needs write access
+//! - `DELETE /v1/notes/{id}` permanently deletes a note, owner only
+//!
+//! notes are returned as `SerializedNote` json, errors as `{ "error": "..." }`.
+//! only a note's owner sees its share link and everyone it's shared with.
+//! the full description lives at `GET /v1/openapi.json`, which needs no token
+
+use crate::app::NoteEvent;
+use crate::model::{AccessLevel, NoteValidationError, SerializedNote, SerializedUser, TimeDate};
+use crate::store;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, ResponseError};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum ApiError {
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    BadRequest(String),
+    Invalid(NoteValidationError),
+    Internal(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unauthorized => write!(f, "missing or invalid access token"),
+            Self::Forbidden => write!(f, "you don't have access to that note"),
+            Self::NotFound => write!(f, "no such note"),
+            Self::BadRequest(reason) => write!(f, "{}", reason),
+            Self::Invalid(e) => write!(f, "{}", e),
+            Self::Internal(reason) => write!(f, "internal error: {}", reason),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct ErrorBody {
+    pub(crate) error: String,
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody { error: self.to_string() })
+    }
+}
+
+impl From<NoteValidationError> for ApiError {
+    fn from(e: NoteValidationError) -> Self {
+        Self::Invalid(e)
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Internal(e.to_string())
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// `last_used` is only bumped once it's at least this many seconds old
+const LAST_USED_RESOLUTION: i64 = 60;
+
+/// tokens are only ever stored hashed, so a leaked database doesn't leak working tokens
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn authenticate(req: &HttpRequest) -> ApiResult<SerializedUser> {
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(ApiError::Unauthorized)?;
+
+    let hash = hash_token(token.trim());
+
+    let lifetime = crate::config::get().session_lifetime();
+
+    let (user, stale) = store::read(|store| {
+        let token = store.access_tokens.get(&hash)?;
+
+        let last_active = token.last_used.as_ref().unwrap_or(&token.created);
+        if lifetime.is_some_and(|lifetime| last_active.offset_by(lifetime).has_passed()) {
+            return None;
+        }
+
+        let stale = token.last_used.as_ref().is_none_or(|last_used| last_used.offset_by(LAST_USED_RESOLUTION).has_passed());
+        Some((store.users.get(&token.user).cloned()?, stale))
+    })
+    .ok_or(ApiError::Unauthorized)?;
+
+    //scripts can hit the api many times a second, rewriting the whole store for each one isn't worth it
+    if stale {
+        store::write(|store| {
+            if let Some(token) = store.access_tokens.get_mut(&hash) {
+                token.last_used = Some(TimeDate::default());
+            }
+        })?;
+    }
+
+    Ok(user)
+}
+
+#[derive(Deserialize)]
+pub struct NoteFilter {
+    pub pinned: Option<bool>,
+    pub tag: Option<String>,
+    pub q: Option<String>,
+    pub overdue: Option<bool>,
+}
+
+impl NoteFilter {
+    fn matches(&self, note: &SerializedNote) -> bool {
+        let query = self.q.as_ref().map(|q| q.to_lowercase());
+
+        self.pinned.is_none_or(|pinned| note.pinned == pinned)
+            && self.tag.as_ref().is_none_or(|tag| note.tags.contains(tag))
+            && query.is_none_or(|q| note.title.to_lowercase().contains(&q) || note.body.to_lowercase().contains(&q))
+            && self.overdue.is_none_or(|overdue| note.due.as_ref().is_some_and(TimeDate::has_passed) == overdue)
+    }
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct CreateNote {
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    pub due: Option<TimeDate>,
+}
+
+/// lets a patch tell "leave it alone" (missing) apart from "clear it" (`null`)
+fn double_option<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<T>>, D::Error> {
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct PatchNote {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub pinned: Option<bool>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub due: Option<Option<TimeDate>>,
+}
+
+async fn me(req: HttpRequest) -> ApiResult<web::Json<SerializedUser>> {
+    authenticate(&req).map(web::Json)
+}
+
+async fn list_notes(req: HttpRequest, filter: web::Query<NoteFilter>) -> ApiResult<web::Json<Vec<SerializedNote>>> {
+    let user = authenticate(&req)?;
+
+    let mut notes = store::read(|store| {
+        store
+            .notes
+            .values()
+            .filter(|note| note.access_level(user.uuid).is_some() && filter.matches(note))
+            .map(|note| note.clone().visible_to(user.uuid))
+            .collect::<Vec<_>>()
+    });
+
+    notes.sort_by(|a, b| b.date.cmp(&a.date));
+    Ok(web::Json(notes))
+}
+
+async fn create_note(req: HttpRequest, new_note: web::Json<CreateNote>) -> ApiResult<HttpResponse> {
+    let user = authenticate(&req)?;
+    let CreateNote { title, body, tags, pinned, due } = new_note.into_inner();
+    crate::config::get().note_limits().check(&title, &body)?;
+
+    let mut note = SerializedNote::new(title, body, user);
+    note.tags = tags;
+    note.pinned = pinned;
+    note.due = due;
+
+    store::write(|store| {
+        store.notes.insert(note.id, note.clone());
+    })?;
+
+    crate::webhooks::dispatch(&note, &[NoteEvent::Created]);
+    Ok(HttpResponse::Created().json(note))
+}
+
+fn parse_id(id: &str) -> ApiResult<Uuid> {
+    Uuid::parse_str(id).map_err(|_| ApiError::BadRequest(format!("{} is not a valid note id", id)))
+}
+
+async fn get_note(req: HttpRequest, id: web::Path<String>) -> ApiResult<web::Json<SerializedNote>> {
+    let user = authenticate(&req)?;
+    let id = parse_id(&id)?;
+
+    let note = store::read(|store| store.notes.get(&id).cloned()).ok_or(ApiError::NotFound)?;
+
+    match note.access_level(user.uuid) {
+        Some(_) => Ok(web::Json(note.visible_to(user.uuid))),
+        None => Err(ApiError::NotFound),
+    }
+}
+
+async fn patch_note(req: HttpRequest, id: web::Path<String>, patch: web::Json<PatchNote>) -> ApiResult<web::Json<SerializedNote>> {
+    let user = authenticate(&req)?;
+    let id = parse_id(&id)?;
+    let PatchNote { title, body, tags, pinned, due } = patch.into_inner();
+
+    let (previous, note) = store::write(|store| {
+        let note = store.notes.get_mut(&id).ok_or(ApiError::NotFound)?;
+        let previous = note.clone();
+
+        match note.access_level(user.uuid) {
+            Some(AccessLevel::Write) => {}
+            Some(AccessLevel::Read) => return Err(ApiError::Forbidden),
+            None => return Err(ApiError::NotFound),
+        }
+
+        crate::config::get().note_limits().check_edit(
+            title.as_ref().unwrap_or(&note.title),
+            body.as_ref().unwrap_or(&note.body),
+            &previous.title,
+            &previous.body,
+        )?;
+
+        if let Some(title) = title {
+            note.title = title;
+        }
+        if let Some(body) = body {
+            note.body = body;
+        }
+        if let Some(tags) = tags {
+            note.tags = tags;
+        }
+        if let Some(pinned) = pinned {
+            note.pinned = pinned;
+        }
+        if let Some(due) = due {
+            note.due = due;
+        }
+        note.last_edit = Some(TimeDate::default());
+
+        Ok((previous, note.clone()))
+    })??;
+
+    crate::webhooks::dispatch(&note, &crate::webhooks::events_between(Some(&previous), &note));
+    Ok(web::Json(note.visible_to(user.uuid)))
+}
+
+async fn delete_note(req: HttpRequest, id: web::Path<String>) -> ApiResult<HttpResponse> {
+    let user = authenticate(&req)?;
+    let id = parse_id(&id)?;
+
+    let removed = store::write(|store| {
+        let note = store.notes.get(&id).ok_or(ApiError::NotFound)?;
+
+        if note.user.uuid != user.uuid {
+            return Err(if note.access_level(user.uuid).is_some() { ApiError::Forbidden } else { ApiError::NotFound });
+        }
+
+        let removed = store.notes.remove(&id).unwrap();
+        store.share_links.retain(|_, link| link.note_id != id);
+
+        let referenced = store.referenced_blobs();
+        crate::attachments::remove_unreferenced(removed.attachments.iter().map(|a| a.blob.as_str()), &referenced);
+
+        Ok(removed)
+    })??;
+
+    crate::webhooks::dispatch(&removed, &[NoteEvent::Deleted]);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn openapi() -> HttpResponse {
+    HttpResponse::Ok().json(crate::openapi::document())
+}
+
+/// the `/v1` routes, see the module docs for what each one does
+pub fn service() -> actix_web::Scope {
+    web::scope("/v1")
+        .app_data(web::JsonConfig::default().error_handler(|e, _| ApiError::BadRequest(e.to_string()).into()))
+        .app_data(web::QueryConfig::default().error_handler(|e, _| ApiError::BadRequest(e.to_string()).into()))
+        .route("/openapi.json", web::get().to(openapi))
+        .route("/me", web::get().to(me))
+        .route("/notes", web::get().to(list_notes))
+        .route("/notes", web::post().to(create_note))
+        .route("/notes/{id}", web::get().to(get_note))
+        .route("/notes/{id}", web::patch().to(patch_note))
+        .route("/notes/{id}", web::delete().to(delete_note))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AccessToken;
+    use crate::model::ShareLink;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    /// a fresh user with a token, returns the user and the `Authorization` header for it
+    fn user_with_token(name: &str) -> (SerializedUser, String) {
+        let user = SerializedUser { username: format!("{}-{}", name, Uuid::new_v4()), creation_date: TimeDate::default(), uuid: Uuid::new_v4() };
+        let secret = Uuid::new_v4().simple().to_string();
+        let token = AccessToken { id: Uuid::new_v4(), user: user.uuid, name: "tests".to_owned(), created: TimeDate::default(), last_used: None };
+
+        store::write(|store| {
+            store.users.insert(user.uuid, user.clone());
+            store.access_tokens.insert(hash_token(&secret), token);
+        })
+        .unwrap();
+
+        (user, format!("Bearer {}", secret))
+    }
+
+    fn insert(note: SerializedNote) -> SerializedNote {
+        store::write(|store| store.notes.insert(note.id, note.clone())).unwrap();
+        note
+    }
+
+    #[actix_web::test]
+    async fn bearer_tokens_are_checked() {
+        let app = test::init_service(App::new().service(service())).await;
+        let (user, auth) = user_with_token("bearer");
+
+        let missing = test::call_service(&app, test::TestRequest::get().uri("/v1/me").to_request()).await;
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+
+        let wrong = test::TestRequest::get().uri("/v1/me").insert_header(("Authorization", "Bearer not-a-token")).to_request();
+        assert_eq!(test::call_service(&app, wrong).await.status(), StatusCode::UNAUTHORIZED);
+
+        let not_bearer = test::TestRequest::get().uri("/v1/me").insert_header(("Authorization", auth.replace("Bearer", "Basic"))).to_request();
+        assert_eq!(test::call_service(&app, not_bearer).await.status(), StatusCode::UNAUTHORIZED);
+
+        let me: SerializedUser = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/v1/me").insert_header(("Authorization", auth)).to_request()).await;
+        assert_eq!(me, user);
+    }
+
+    #[actix_web::test]
+    async fn list_filters_narrow_the_notes() {
+        let app = test::init_service(App::new().service(service())).await;
+        let (user, auth) = user_with_token("filters");
+
+        let mut pinned = SerializedNote::new("Groceries".to_owned(), "milk and eggs".to_owned(), user.clone());
+        pinned.pinned = true;
+        let pinned = insert(pinned);
+        let mut tagged = SerializedNote::new("Reading list".to_owned(), "a book about Eggs".to_owned(), user.clone());
+        tagged.tags = vec!["books".to_owned()];
+        let tagged = insert(tagged);
+        let plain = insert(SerializedNote::new("Plain".to_owned(), String::new(), user));
+
+        let ids = |query: &str| {
+            let request = test::TestRequest::get().uri(&format!("/v1/notes{}", query)).insert_header(("Authorization", auth.clone())).to_request();
+            let app = &app;
+            async move {
+                let mut ids = test::call_and_read_body_json::<_, _, Vec<SerializedNote>>(app, request).await.into_iter().map(|note| note.id).collect::<Vec<_>>();
+                ids.sort();
+                ids
+            }
+        };
+        let sorted = |mut ids: Vec<Uuid>| {
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids("").await, sorted(vec![pinned.id, tagged.id, plain.id]));
+        assert_eq!(ids("?pinned=true").await, vec![pinned.id]);
+        assert_eq!(ids("?pinned=false").await, sorted(vec![tagged.id, plain.id]));
+        assert_eq!(ids("?tag=books").await, vec![tagged.id]);
+        assert_eq!(ids("?q=eggs").await, sorted(vec![pinned.id, tagged.id]));
+        assert_eq!(ids("?q=eggs&pinned=false").await, vec![tagged.id]);
+
+        let bad = test::TestRequest::get().uri("/v1/notes?pinned=maybe").insert_header(("Authorization", auth.clone())).to_request();
+        assert_eq!(test::call_service(&app, bad).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn patches_leave_missing_fields_alone_and_clear_null_ones() {
+        let app = test::init_service(App::new().service(service())).await;
+        let (user, auth) = user_with_token("patch");
+
+        let mut note = SerializedNote::new("Taxes".to_owned(), "file them".to_owned(), user);
+        note.due = Some(TimeDate::default());
+        let note = insert(note);
+
+        let patch = |body: Value| test::TestRequest::patch().uri(&format!("/v1/notes/{}", note.id)).insert_header(("Authorization", auth.clone())).set_json(body).to_request();
+
+        let renamed: SerializedNote = test::call_and_read_body_json(&app, patch(json!({ "title": "Tax return" }))).await;
+        assert_eq!(renamed.title, "Tax return");
+        assert_eq!(renamed.body, "file them");
+        assert_eq!(renamed.due, note.due);
+
+        let cleared: SerializedNote = test::call_and_read_body_json(&app, patch(json!({ "due": null }))).await;
+        assert_eq!(cleared.title, "Tax return");
+        assert_eq!(cleared.due, None);
+        assert_eq!(store::read(|store| store.notes[&note.id].due.clone()), None);
+    }
+
+    #[actix_web::test]
+    async fn collaborators_only_see_their_own_access() {
+        let app = test::init_service(App::new().service(service())).await;
+        let (owner, owner_auth) = user_with_token("owner");
+        let (reader, reader_auth) = user_with_token("reader");
+        let (writer, _) = user_with_token("writer");
+
+        let mut note = SerializedNote::new("Shared".to_owned(), String::new(), owner);
+        note.share_with(reader.clone(), AccessLevel::Read);
+        note.share_with(writer.clone(), AccessLevel::Write);
+        note.share_link = Some(ShareLink::new(note.id, None));
+        let note = insert(note);
+
+        let get = |auth: &str| test::TestRequest::get().uri(&format!("/v1/notes/{}", note.id)).insert_header(("Authorization", auth.to_owned())).to_request();
+
+        let as_owner: SerializedNote = test::call_and_read_body_json(&app, get(&owner_auth)).await;
+        assert_eq!(as_owner.access.len(), 2);
+        assert_eq!(as_owner.share_link, note.share_link);
+
+        let as_reader: SerializedNote = test::call_and_read_body_json(&app, get(&reader_auth)).await;
+        assert_eq!(as_reader.access.iter().map(|entry| entry.user.uuid).collect::<Vec<_>>(), vec![reader.uuid]);
+        assert_eq!(as_reader.share_link, None);
+
+        let patch = test::TestRequest::patch().uri(&format!("/v1/notes/{}", note.id)).insert_header(("Authorization", reader_auth)).set_json(json!({ "title": "mine now" })).to_request();
+        assert_eq!(test::call_service(&app, patch).await.status(), StatusCode::FORBIDDEN);
+    }
+}
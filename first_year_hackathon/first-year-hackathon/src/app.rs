@@ -1,11 +1,14 @@
+use crate::model::*;
 use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
 use chrono::prelude::*;
 use uuid::Uuid;
-use leptos::html::Input;
+use leptos::html::{Input, Textarea};
 use serde::{Serialize, Deserialize};
 
+//only read by the commented out localstorage code below
+#[allow(dead_code)]
 const STORAGE_KEY: &str = "notes-app";
 
 #[component]
@@ -13,6 +16,30 @@ pub fn App(cx: Scope) -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context(cx);
 
+    //a stand in until someone signs in, nothing is loaded or saved for it
+    let user = create_rw_signal(cx, User::new_user(String::new()));
+    provide_context(cx, user);
+
+    let signed_in = SignedIn(create_rw_signal(cx, None));
+    provide_context(cx, signed_in);
+
+    let limits = create_rw_signal(cx, NoteLimits::default());
+    provide_context(cx, limits);
+
+    //pick the session back up if the browser still has one
+    create_effect(cx, move |_| {
+        spawn_local(async move {
+            match signed_in_user(cx).await {
+                Ok(stored) => signed_in.set(cx, stored),
+                Err(e) => log!("failed to load user: {}", e),
+            }
+
+            match get_note_limits().await {
+                Ok(server_limits) => limits.set(server_limits),
+                Err(e) => log!("failed to load note limits: {}", e),
+            }
+        });
+    });
 
     view! { cx,
         <Stylesheet id="leptos" href="/pkg/leptos_start.css"/>
@@ -23,6 +50,8 @@ pub fn App(cx: Scope) -> impl IntoView {
             <main>
                 <Routes>
                     <Route path="" view=HomePage/>
+                    <Route path="/s/:token" view=SharedNotePage/>
+                    <Route path="/settings" view=|cx| view!{cx, <SignedInOnly><SettingsPage/></SignedInOnly>}/>
                     <Route path="/*any" view=NotFound/>
                 </Routes>
             </main>
@@ -34,30 +63,417 @@ pub fn App(cx: Scope) -> impl IntoView {
 #[component]
 fn HomePage(cx: Scope) -> impl IntoView {
 
-    let user_ops = create_rw_signal(cx, User::new_user("will".to_owned()));
+    let user_ops = expect_context::<RwSignal<User>>(cx);
 
     view! { cx,
-        <NotesHome user=user_ops/>
+        <SignedInOnly>
+            <NotesHome user=user_ops/>
+        </SignedInOnly>
+    }
+}
+
+/// whether the browser has a session, None until the server has been asked
+#[derive(Clone, Copy)]
+struct SignedIn(RwSignal<Option<bool>>);
+
+impl SignedIn {
+    fn set(&self, cx: Scope, stored: Option<SerializedUser>) {
+        if let Some(stored) = &stored {
+            expect_context::<RwSignal<User>>(cx).set(stored.clone().into_normal());
+        }
+        self.0.set(Some(stored.is_some()));
+    }
+}
+
+/// shows its children once someone has signed in, and the sign in form until then
+#[component]
+fn SignedInOnly(cx: Scope, children: ChildrenFn) -> impl IntoView {
+    let SignedIn(signed_in) = expect_context(cx);
+
+    move || match signed_in.get() {
+        None => ().into_view(cx),
+        Some(true) => children(cx).into_view(cx),
+        Some(false) => view!{cx, <SignInForm/>}.into_view(cx),
+    }
+}
+
+#[component]
+fn SignInForm(cx: Scope) -> impl IntoView {
+    let signed_in = expect_context::<SignedIn>(cx);
+
+    let (username, set_username) = create_signal(cx, String::new());
+    let (password, set_password) = create_signal(cx, String::new());
+    let (error, set_error) = create_signal(cx, None::<String>);
+
+    let submit = move |new_account: bool| {
+        let (username, password) = (username.get_untracked(), password.get_untracked());
+        spawn_local(async move {
+            let result = if new_account {
+                sign_up(cx, username, password).await
+            } else {
+                sign_in(cx, username, password).await
+            };
+
+            match result {
+                Ok(user) => signed_in.set(cx, Some(user)),
+                Err(e) => set_error.set(Some(e.to_string())),
+            }
+        });
+    };
+
+    view!{
+        cx,
+        <div class="settings">
+            <h1 class="note_title">"sign in"</h1>
+            <input type="text" class="reset bottom_border single_line_text_input" placeholder="username" prop:value=move || username.get() on:input=move |e| set_username.set(event_target_value(&e))/>
+            <input type="password" class="reset bottom_border single_line_text_input" placeholder="password" prop:value=move || password.get() on:input=move |e| set_password.set(event_target_value(&e)) on:keydown=move |e: ev::KeyboardEvent| if e.key() == "Enter" { submit(false) }/>
+
+            <div class="flex_seperator">
+                <button class="reset share_btn" on:click=move |_| submit(false)> "sign in" </button>
+                <button class="reset share_btn" on:click=move |_| submit(true)> "make an account" </button>
+            </div>
+
+            {move || error.get().map(|error| view!{cx, <h1 class="reset small_details">{error}</h1>})}
+        </div>
+    }
+}
+
+/// personal access tokens for the json api
+#[component]
+fn SettingsPage(cx: Scope) -> impl IntoView {
+    let user = expect_context::<RwSignal<User>>(cx);
+    let user_id = create_memo(cx, move |_| user.with(|user| user.uuid));
+
+    let tokens = create_rw_signal(cx, Vec::<AccessToken>::new());
+    let (token_name, set_token_name) = create_signal(cx, String::new());
+    let (new_secret, set_new_secret) = create_signal(cx, None::<String>);
+
+    //signing in happens on load, so refetch once the stored user arrives
+    create_effect(cx, move |_| {
+        user_id.track();
+        spawn_local(async move {
+            match list_access_tokens(cx).await {
+                Ok(list) => tokens.set(list),
+                Err(e) => log!("failed to load access tokens: {}", e),
+            }
+        });
+    });
+
+    let create_token = move || {
+        let name = filter_input(token_name.get());
+        if name.is_empty() {
+            return;
+        }
+
+        spawn_local(async move {
+            match create_access_token(cx, name).await {
+                Ok((token, secret)) => {
+                    tokens.update(|tokens| tokens.push(token));
+                    set_new_secret.set(Some(secret));
+                    set_token_name.set(String::new());
+                }
+                Err(e) => log!("failed to create access token: {}", e),
+            }
+        });
+    };
+
+    let revoke_token = move |id: Uuid| {
+        spawn_local(async move {
+            match revoke_access_token(cx, id).await {
+                Ok(()) => tokens.update(|tokens| tokens.retain(|token| token.id != id)),
+                Err(e) => log!("failed to revoke access token: {}", e),
+            }
+        });
+    };
+
+    view!{
+        cx,
+        <div class="settings">
+            <A href="/">"back to notes"</A>
+            <h1 class="note_title">"access tokens"</h1>
+            <p class="note_text_body">"tokens let scripts use the notes api at /v1 as you, send them as an Authorization: Bearer header"</p>
+
+            <div class="flex_seperator">
+                <input type="text" class="reset bottom_border single_line_text_input" placeholder="what is this token for?" prop:value=move || token_name.get() on:input=move |e| set_token_name.set(event_target_value(&e))/>
+                <button class="reset share_btn" on:click=move |_| create_token()> "create token" </button>
+            </div>
+
+            {move || new_secret.get().map(|secret| view!{
+                cx,
+                <div class="new_token">
+                    <h1 class="reset small_details">"copy this now, it won't be shown again"</h1>
+                    <code>{secret}</code>
+                </div>
+            })}
+
+            <For
+                each=move || tokens.get()
+                key=|token| token.id
+                view=move |cx, token: AccessToken| {
+                    let id = token.id;
+                    view!{
+                        cx,
+                        <div class="flex_seperator text_padding">
+                            <h1 class="reset small_details">{token.name.clone()}</h1>
+                            <h1 class="reset small_details">"created "{token.created.format_date()}</h1>
+                            <h1 class="reset small_details">{token.last_used.clone().map_or("never used".to_owned(), |used| format!("last used {}", used.format_datetime()))}</h1>
+                            <button class="reset share_btn" on:click=move |_| revoke_token(id)> "revoke" </button>
+                        </div>
+                    }
+                }
+            />
+
+            <h1 class="note_title">"feeds"</h1>
+            <p class="note_text_body">"notes with a public link show up in your feeds"</p>
+            {move || user.with(|user| {
+                let atom = format!("/feeds/{}/atom.xml", user.username);
+                let rss = format!("/feeds/{}/rss.xml", user.username);
+                view!{
+                    cx,
+                    <div class="flex_seperator text_padding">
+                        <a class="reset small_details" href=atom.clone() rel="external">{atom}</a>
+                        <a class="reset small_details" href=rss.clone() rel="external">{rss}</a>
+                    </div>
+                }
+            })}
+
+            <CalendarSettings/>
+
+            <WebhookSettings user_id/>
+        </div>
+    }
+}
+
+#[component]
+fn CalendarSettings(cx: Scope) -> impl IntoView {
+    let (link, set_link) = create_signal(cx, None::<String>);
+
+    let reset_link = move || {
+        spawn_local(async move {
+            match reset_calendar_link(cx, browser_timezone()).await {
+                Ok(path) => {
+                    let origin = window().location().origin().unwrap_or_default();
+                    set_link.set(Some(format!("{}{}", origin, path)));
+                }
+                Err(e) => log!("failed to make calendar link: {}", e),
+            }
+        });
+    };
+
+    let revoke_link = move || {
+        spawn_local(async move {
+            match revoke_calendar_link(cx).await {
+                Ok(()) => set_link.set(None),
+                Err(e) => log!("failed to revoke calendar link: {}", e),
+            }
+        });
+    };
+
+    view!{
+        cx,
+        <div class="calendar_link">
+            <h1 class="note_title">"calendar"</h1>
+            <p class="note_text_body">"subscribe to due dates and reminders from your calendar app, a new link stops the old one working"</p>
+
+            <div class="flex_seperator">
+                <button class="reset share_btn" on:click=move |_| reset_link()> "new calendar link" </button>
+                <button class="reset share_btn" on:click=move |_| revoke_link()> "revoke calendar link" </button>
+            </div>
+
+            {move || link.get().map(|link| view!{
+                cx,
+                <div class="new_token">
+                    <h1 class="reset small_details">"copy this now, it won't be shown again"</h1>
+                    <code>{link}</code>
+                </div>
+            })}
+        </div>
+    }
+}
+
+#[component]
+fn WebhookSettings(cx: Scope, user_id: Memo<Uuid>) -> impl IntoView {
+    let hooks = create_rw_signal(cx, Vec::<Webhook>::new());
+    let (url, set_url) = create_signal(cx, String::new());
+    let (events, set_events) = create_signal(cx, NoteEvent::ALL.to_vec());
+    let (error, set_error) = create_signal(cx, None::<String>);
+
+    let load_hooks = move || {
+        spawn_local(async move {
+            match list_webhooks(cx).await {
+                Ok(list) => hooks.set(list),
+                Err(e) => log!("failed to load webhooks: {}", e),
+            }
+        });
+    };
+
+    create_effect(cx, move |_| {
+        user_id.track();
+        load_hooks();
+    });
+
+    let toggle_event = move |event: NoteEvent| set_events.update(|events| {
+        match events.iter().position(|e| *e == event) {
+            Some(index) => { events.remove(index); }
+            None => events.push(event),
+        }
+    });
+
+    let add_hook = move || {
+        let target = filter_input(url.get());
+        if target.is_empty() || events.with(Vec::is_empty) {
+            return;
+        }
+
+        let subscribed = events.get();
+        spawn_local(async move {
+            match create_webhook(cx, target, subscribed).await {
+                Ok(hook) => {
+                    hooks.update(|hooks| hooks.push(hook));
+                    set_url.set(String::new());
+                    set_error.set(None);
+                }
+                Err(e) => set_error.set(Some(e.to_string())),
+            }
+        });
+    };
+
+    let remove_hook = move |id: Uuid| {
+        spawn_local(async move {
+            match delete_webhook(cx, id).await {
+                Ok(()) => hooks.update(|hooks| hooks.retain(|hook| hook.id != id)),
+                Err(e) => log!("failed to delete webhook: {}", e),
+            }
+        });
+    };
+
+    view!{
+        cx,
+        <div class="webhooks">
+            <h1 class="note_title">"webhooks"</h1>
+            <p class="note_text_body">"payloads are signed with the webhook secret, check the X-Notes-Signature header (sha256 hmac of the body)"</p>
+
+            <div class="flex_seperator">
+                <input type="url" class="reset bottom_border single_line_text_input" placeholder="https://example.com/hook" prop:value=move || url.get() on:input=move |e| set_url.set(event_target_value(&e))/>
+                {NoteEvent::ALL.into_iter().map(|event| view!{
+                    cx,
+                    <label class="reset small_details">
+                        {event.name()}
+                        <input type="checkbox" prop:checked=move || events.with(|events| events.contains(&event)) on:change=move |_| toggle_event(event)/>
+                    </label>
+                }).collect_view(cx)}
+                <button class="reset share_btn" on:click=move |_| add_hook()> "add webhook" </button>
+            </div>
+
+            {move || error.get().map(|error| view!{cx, <h1 class="reset small_details">{error}</h1>})}
+
+            <button class="reset share_btn" on:click=move |_| load_hooks()> "refresh delivery logs" </button>
+
+            <For
+                each=move || hooks.get()
+                key=|hook| (hook.id, hook.deliveries.first().map(|delivery| (delivery.id, delivery.attempt)))
+                view=move |cx, hook: Webhook| {
+                    let id = hook.id;
+                    let events = hook.events.iter().map(NoteEvent::name).collect::<Vec<_>>().join(", ");
+                    view!{
+                        cx,
+                        <div class="webhook">
+                            <div class="flex_seperator text_padding">
+                                <h1 class="reset small_details">{hook.url.clone()}</h1>
+                                <h1 class="reset small_details">{events}</h1>
+                                <button class="reset share_btn" on:click=move |_| remove_hook(id)> "delete" </button>
+                            </div>
+                            <h1 class="reset small_details">"secret "<code>{hook.secret.clone()}</code></h1>
+                            <ul class="reset delivery_log">
+                                {hook.deliveries.iter().map(|delivery| {
+                                    let outcome = match (&delivery.status, &delivery.error) {
+                                        (_, None) => "delivered".to_owned(),
+                                        (Some(status), Some(_)) => format!("failed ({})", status),
+                                        (None, Some(error)) => format!("failed ({})", error),
+                                    };
+                                    view!{
+                                        cx,
+                                        <li class="small_details" class:delivery_failed=!delivery.success>
+                                            {format!("{} {} attempt {} {}", delivery.attempted.format_datetime(), delivery.event.name(), delivery.attempt, outcome)}
+                                        </li>
+                                    }
+                                }).collect_view(cx)}
+                            </ul>
+                        </div>
+                    }
+                }
+            />
+        </div>
+    }
+}
+
+/// read only view of a single note reached through a public share link
+#[component]
+fn SharedNotePage(cx: Scope) -> impl IntoView {
+    let params = use_params_map(cx);
+    let token = move || params.with(|params| params.get("token").cloned().unwrap_or_default());
+
+    let shared_note = create_resource(cx, token, get_shared_note);
+
+    view!{
+        cx,
+        <Suspense fallback=move || view!{cx, <h1 class="reset small_details">"loading..."</h1>}>
+            {move || shared_note.read(cx).map(|note| match note {
+                Ok(Some(note)) => view!{
+                    cx,
+                    <div class="note_body shared_note">
+                        <div class="flex_seperator text_padding">
+                            <h1 class="reset small_details"> {note.date.format_datetime()} </h1>
+                            <h1 class="reset small_details">"@"{note.user.username.clone()}</h1>
+                        </div>
+                        <div class="text_bounding_area">
+                            <h1 class="note_title"> {note.title.clone()} </h1>
+                            {if note.kind == NoteKind::Checklist {
+                                view!{
+                                    cx,
+                                    <ul class="reset checklist">
+                                        {note.items.iter().map(|item| view!{
+                                            cx,
+                                            <li class="checklist_item" class:checklist_done=item.done>
+                                                <input type="checkbox" prop:checked=item.done disabled/>
+                                                <span>{item.text.clone()}</span>
+                                            </li>
+                                        }).collect_view(cx)}
+                                    </ul>
+                                }.into_view(cx)
+                            }else{
+                                view!{cx, <div class="note_text_body markdown" inner_html=render_markdown(&note.body)/>}.into_view(cx)
+                            }}
+                            {note.last_edit.clone().map(|datetime| view!{cx, <h1 class="previous_edit_timestamp">"last edit: "{datetime.format_datetime()}</h1>})}
+                        </div>
+                    </div>
+                }.into_view(cx),
+                _ => view!{cx, <NotFound/>}.into_view(cx),
+            })}
+        </Suspense>
     }
 }
 
+//sessions aren't wired up yet, the landing page is a placeholder until they are
+#[allow(dead_code)]
 struct SessionId(String);
 
+#[allow(dead_code)]
 impl SessionId {
     fn try_fetch() -> Option<Self> {
         todo!();
     }
 
-    fn into_user(&self) -> User {
+    fn into_user(self) -> User {
         todo!()
     }
 }
 
 #[component]
 fn LandingPage(cx: Scope) -> impl IntoView {
-    let (session_id, set_session_id) = create_signal(cx, SessionId::try_fetch());
+    let (_session_id, set_session_id) = create_signal(cx, SessionId::try_fetch());
 
-    let sign_in_click = move |id| set_session_id.set(Some(id));
+    let _sign_in_click = move |id| set_session_id.set(Some(id));
 
     view!{
         cx,
@@ -79,9 +495,138 @@ pub struct Note {
     pub maximized: RwSignal<bool>,
     pub minimized: RwSignal<bool>,
     pub is_editing: RwSignal<bool>,
+    pub access: RwSignal<Vec<NoteAccess>>,
+    pub share_link: RwSignal<Option<ShareLink>>,
+    pub comments: RwSignal<Vec<Comment>>,
+    pub kind: RwSignal<NoteKind>,
+    pub items: RwSignal<Vec<ChecklistItem>>,
+    pub attachments: RwSignal<Vec<Attachment>>,
+    pub due: RwSignal<Option<TimeDate>>,
+    pub reminder: RwSignal<Option<TimeDate>>,
+    pub reminder_sent: RwSignal<bool>,
+    pub recurrence: RwSignal<Option<Recurrence>>,
+    pub links: RwSignal<Vec<NoteLink>>,
+    pub tags: RwSignal<Vec<String>>,
+    pub in_feed: RwSignal<bool>,
     colour: String,
 }
 
+/// turns "work, ideas ,, todo" into ["work", "ideas", "todo"]
+fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags = Vec::<String>::new();
+    for tag in input.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_owned());
+        }
+    }
+    tags
+}
+
+/// a reusable starting point for new notes, `{{date}}`, `{{time}}` and `{{user}}`
+/// get filled in when a note is created from it
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct NoteTemplate {
+    pub id: Uuid,
+    pub owner: Uuid,
+    pub name: String,
+    pub title: String,
+    pub body: String,
+    pub tags: Vec<String>,
+    pub colour: String,
+    pub kind: NoteKind,
+    pub items: Vec<String>,
+}
+
+impl NoteTemplate {
+    fn from_note(note: &Note, owner: &User) -> Self {
+        let title = note.title.get();
+        let name = if title.is_empty() { "untitled template".to_owned() } else { title.clone() };
+
+        Self {
+            id: Uuid::new_v4(),
+            owner: owner.uuid,
+            name,
+            title,
+            body: note.body.get(),
+            tags: note.tags.get(),
+            colour: note.colour.clone(),
+            kind: note.kind.get(),
+            items: note.items.get().into_iter().map(|item| item.text).collect(),
+        }
+    }
+}
+
+fn fill_placeholders(text: &str, user: &User) -> String {
+    let now = TimeDate::default();
+
+    text.replace("{{date}}", &now.format_date())
+        .replace("{{time}}", &now.format_time())
+        .replace("{{user}}", &user.username)
+}
+
+/// every `[[label]]` in a body in order of appearance, with the byte range of the
+/// whole link as written and the label with surrounding whitespace trimmed
+fn wiki_links(body: &str) -> Vec<(std::ops::Range<usize>, String)> {
+    let mut links = Vec::new();
+    let mut offset = 0;
+
+    while let Some(start) = body[offset..].find("[[").map(|start| offset + start) {
+        let Some(end) = body[start + 2..].find("]]").map(|end| start + 2 + end) else {
+            break;
+        };
+
+        let label = body[start + 2..end].trim();
+        if !label.is_empty() && !label.contains('\n') && !label.contains("[[") {
+            links.push((start..end + 2, label.to_owned()));
+        }
+        offset = end + 2;
+    }
+
+    links
+}
+
+fn wiki_link_labels(body: &str) -> Vec<String> {
+    wiki_links(body).into_iter().map(|(_, label)| label).collect()
+}
+
+/// the partial label of an unclosed `[[` right before the cursor, if there is one
+fn open_wiki_link(before_cursor: &str) -> Option<(usize, String)> {
+    let start = before_cursor.rfind("[[")?;
+    let partial = &before_cursor[start + 2..];
+
+    (!partial.contains("]]") && !partial.contains('\n')).then(|| (start, partial.to_owned()))
+}
+
+fn escape_markdown_link_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]")
+}
+
+async fn upload_attachment(file: web_sys::File) -> Result<Attachment, String> {
+    if file.size() as u64 > MAX_ATTACHMENT_BYTES {
+        return Err(format!("{} is larger than {} MB", file.name(), MAX_ATTACHMENT_BYTES / (1024 * 1024)));
+    }
+
+    let content_type = file.type_();
+    if !ALLOWED_ATTACHMENT_TYPES.iter().any(|(allowed, _)| *allowed == content_type) {
+        return Err(format!("{} files can't be attached", content_type));
+    }
+
+    let response = gloo_net::http::Request::post("/attachments")
+        .header("Content-Type", &content_type)
+        .header("X-File-Name", &file.name())
+        .body(file)
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.ok() {
+        return Err(response.text().await.unwrap_or_else(|_| response.status_text()));
+    }
+
+    response.json::<Attachment>().await.map_err(|e| e.to_string())
+}
+
 #[derive(PartialEq, Eq, Clone)]
 pub struct User{
     username: String,
@@ -96,11 +641,64 @@ pub struct User{
 }
 
     
-#[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
-struct SerializedUser {
-    username: String,
-    creation_date: TimeDate,
-    uuid: Uuid,
+/// a personal access token for the json api, the secret itself is only ever shown once
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub id: Uuid,
+    pub user: Uuid,
+    pub name: String,
+    pub created: TimeDate,
+    pub last_used: Option<TimeDate>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteEvent {
+    Created,
+    Edited,
+    Pinned,
+    Deleted,
+    Commented,
+}
+
+impl NoteEvent {
+    pub const ALL: [NoteEvent; 5] = [Self::Created, Self::Edited, Self::Pinned, Self::Deleted, Self::Commented];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Created => "created",
+            Self::Edited => "edited",
+            Self::Pinned => "pinned",
+            Self::Deleted => "deleted",
+            Self::Commented => "commented",
+        }
+    }
+}
+
+/// a url that gets a signed post whenever one of `events` happens to one of the user's notes
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub user: Uuid,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<NoteEvent>,
+    pub created: TimeDate,
+    /// newest first
+    #[serde(default)]
+    pub deliveries: Vec<WebhookDelivery>,
+}
+
+/// a single attempt at delivering an event, retries share the delivery id
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub event: NoteEvent,
+    pub attempt: u32,
+    pub attempted: TimeDate,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub success: bool,
 }
 
 impl SerializedUser {
@@ -136,13 +734,13 @@ impl From<&User> for SerializedUser {
         SerializedUser {
             username: username.clone(),
             creation_date: creation_date.clone(),
-            uuid: uuid.clone(),
+            uuid: *uuid,
         }
     }
 }
 
 impl User {
-    fn new_user(username: String) -> Self {
+    pub(crate) fn new_user(username: String) -> Self {
         //TODO: need to check whether username exists within db
         User {
             username,
@@ -155,6 +753,7 @@ impl User {
         }
     }
 
+    #[allow(dead_code)]
     fn new_guest() -> Self {
         User {
             username: String::new(),
@@ -173,77 +772,7 @@ impl User {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
-pub struct TimeDate {
-    epoch_time: i64,
-}
-
-impl TimeDate {
-    const SECONDS_IN_WEEK: i64 = 7 * 24 * 60 * 60;
-
-    pub fn format_date(&self) -> String {
-        let local_date = chrono::Local.timestamp_opt(self.epoch_time, 0).unwrap().date_naive();
-        local_date.to_string()
-    }
-
-    pub fn format_time(&self) -> String {
-        let current_time = chrono::offset::Utc::now().timestamp();
-
-        let comp_time = self.epoch_time;
-
-        if current_time - comp_time > Self::SECONDS_IN_WEEK {
-            return String::new()
-        }
-
-        let time = chrono::Local.timestamp_opt(comp_time, 0).unwrap();
-        let minutes = time.minute();
-
-        let minutes = if minutes < 10 {
-            format!("0{}", minutes)
-        }else{
-            format!("{}", minutes)
-        };
-
-        format!{"{}:{}", time.hour(), minutes}
-    }
-
-    pub fn format_datetime(&self) -> String {
-        let maybe_time = self.format_time();
-        let date = self.format_date();
-
-
-        if maybe_time.is_empty() {
-            date
-        }else{
-            format!{"{} @ {}", date, maybe_time}
-        }
-    }
-
-}
-
-impl Default for TimeDate {
-    fn default() -> Self {
-
-        let local_time = chrono::offset::Local::now();
-        let date = local_time.date_naive().to_string();
-        let time = local_time.time();
-
-
-
-        Self {
-            epoch_time: chrono::offset::Utc::now().timestamp(),
-        }
-    }
-}
-
-fn get_new_pastel_colour() -> String {
-    format!{"rgb({}, {}, {})", fastrand::u8(200..), fastrand::u8(200..), fastrand::u8(200..)}
-}
-
 impl Note {
-    const TITLE_CHAR_LIMIT: u64 = 100;
-    const BODY_CHAR_LIMIT: u64 = 1000;
-
     fn new_with_text(cx: Scope, title: String, user: &User, body: String) -> Self {
         let title = create_rw_signal(cx, title);
         let body = create_rw_signal(cx, body);
@@ -256,6 +785,22 @@ impl Note {
 
         let last_edit = create_rw_signal(cx, None);
 
+        let access = create_rw_signal(cx, Vec::new());
+        let share_link = create_rw_signal(cx, None);
+        let comments = create_rw_signal(cx, Vec::new());
+
+        let kind = create_rw_signal(cx, NoteKind::Text);
+        let items = create_rw_signal(cx, Vec::new());
+        let attachments = create_rw_signal(cx, Vec::new());
+
+        let due = create_rw_signal(cx, None);
+        let reminder = create_rw_signal(cx, None);
+        let reminder_sent = create_rw_signal(cx, false);
+        let recurrence = create_rw_signal(cx, None);
+        let links = create_rw_signal(cx, Vec::new());
+        let tags = create_rw_signal(cx, Vec::new());
+        let in_feed = create_rw_signal(cx, false);
+
         Self {
             title,
             user: user.clone(),
@@ -267,6 +812,19 @@ impl Note {
             maximized,
             minimized,
             is_editing,
+            access,
+            share_link,
+            comments,
+            kind,
+            items,
+            attachments,
+            due,
+            reminder,
+            reminder_sent,
+            recurrence,
+            links,
+            tags,
+            in_feed,
             colour: get_new_pastel_colour()
         }
     }
@@ -276,6 +834,72 @@ impl Note {
         Self::new_with_text(cx, String::new(), user, String::new())
     }
 
+    fn from_template(cx: Scope, template: &NoteTemplate, user: &User) -> Self {
+        let fill = |text: &str| fill_placeholders(text, user);
+
+        let mut note = Self::new_with_text(cx, fill(&template.title), user, fill(&template.body));
+        note.colour = template.colour.clone();
+        note.tags.set(template.tags.iter().map(|tag| fill(tag)).collect());
+        note.kind.set(template.kind);
+        note.items.set(template.items.iter().map(|item| ChecklistItem::new(fill(item))).collect());
+        note
+    }
+
+    /// copies an imported note's contents. the id, owner, creation date, who it's
+    /// shared with and its comments stay as they are
+    fn overwrite_with(&self, other: SerializedNote) {
+        self.title.set(other.title);
+        self.body.set(other.body);
+        self.last_edit.set(other.last_edit);
+        self.kind.set(other.kind);
+        self.items.set(other.items);
+        self.attachments.set(other.attachments);
+        self.due.set(other.due);
+        self.reminder.set(other.reminder);
+        self.reminder_sent.set(other.reminder_sent);
+        self.recurrence.set(other.recurrence);
+        self.links.set(other.links);
+        self.tags.set(other.tags);
+        self.pinned.set(other.pinned);
+    }
+
+    /// copies only what a markdown file or another app's export carries, so updating
+    /// a note from one leaves its due date, attachments and the rest alone
+    fn overwrite_contents_with(&self, other: SerializedNote) {
+        self.title.set(other.title);
+        self.body.set(other.body);
+        self.last_edit.set(other.last_edit);
+        self.kind.set(other.kind);
+        self.items.set(other.items);
+        self.tags.set(other.tags);
+        self.pinned.set(other.pinned);
+    }
+
+    /// puts back what the note editor can change to how it was in `original`, pins,
+    /// comments and sharing that happened in the meantime are left alone
+    fn restore_edits(&self, original: SerializedNote) {
+        fn restore<T: PartialEq>(signal: RwSignal<T>, value: T) {
+            if signal.with_untracked(|current| *current != value) {
+                signal.set(value);
+            }
+        }
+
+        restore(self.title, original.title);
+        restore(self.body, original.body);
+        restore(self.links, original.links);
+        restore(self.tags, original.tags);
+        restore(self.kind, original.kind);
+        restore(self.items, original.items);
+        restore(self.attachments, original.attachments);
+        restore(self.due, original.due);
+        //a reminder that went off mid edit stays sent
+        let sent = original.reminder_sent || (self.reminder.with_untracked(|reminder| *reminder == original.reminder) && self.reminder_sent.get_untracked());
+        restore(self.reminder, original.reminder);
+        restore(self.reminder_sent, sent);
+        restore(self.recurrence, original.recurrence);
+        restore(self.last_edit, original.last_edit);
+    }
+
     pub fn update_title(&self, new_title: String) {
         self.title.set(new_title);
         self.induce_edit();
@@ -290,8 +914,17 @@ impl Note {
         self.last_edit.set(Some(TimeDate::default()));
     }
 
-    fn mirror_to_db(&self) {
-        todo!()
+    /// persists the note. share links are looked up server side, so a note has to be
+    /// in the store before /s/:token can show it
+    fn mirror_to_db(&self, cx: Scope) {
+        let note = SerializedNote::from(self);
+        spawn_local(async move {
+            match save_note(cx, note, false).await {
+                Ok(Ok(())) => {}
+                Ok(Err(invalid)) => log!("note not saved, {}", invalid),
+                Err(e) => log!("failed to save note: {}", e),
+            }
+        });
     }
 
     fn toggle_pinned(&self) {
@@ -310,6 +943,7 @@ impl Note {
         self.minimized.update(|minimized| *minimized = !*minimized);
     }
 
+    #[allow(dead_code)]
     fn toggle_edit(&self) {
         self.is_editing.update(|editing| *editing = !*editing);
     }
@@ -322,27 +956,248 @@ impl Note {
         user.update(|usr| usr.pinned_notes.push(self.clone()));
     }
 
-    //TODO: database mirroring
-    fn delete(&self, notes: &WriteSignal<Vec<Note>>) {
-        notes.update(|ns| ns.retain(|note| note != self))
+    fn set_tags(&self, tags: Vec<String>) {
+        self.tags.set(tags);
+        self.induce_edit();
+    }
+
+    fn delete(&self, cx: Scope, notes: &WriteSignal<Vec<Note>>) {
+        notes.update(|ns| ns.retain(|note| note != self));
 
+        let id = self.id;
+        spawn_local(async move {
+            if let Err(e) = delete_note(cx, id).await {
+                log!("failed to delete note: {}", e);
+            }
+        });
     }
 
-    fn add_pinned(&self, user: &WriteSignal<User>) {
-        user.update(|usr| usr.pinned_notes.push(self.clone()));
+    fn set_due(&self, due: Option<TimeDate>) {
+        //a new due date is also the new day monthly repeats aim for
+        if let (Some(due), Some(Recurrence::Every { interval, unit, .. })) = (&due, self.recurrence.get()) {
+            self.recurrence.set(Some(Recurrence::Every { interval, unit, day: Some(due.to_local().day()) }));
+        }
+        self.due.set(due);
+        self.induce_edit();
     }
 
-    fn remove_pinned(&self, user: &WriteSignal<User>) {
-        user.update(|usr| usr.pinned_notes.retain(|note| note != self))
+    /// changing the reminder re-arms it
+    fn set_reminder(&self, reminder: Option<TimeDate>) {
+        self.reminder.set(reminder);
+        self.reminder_sent.set(false);
+        self.induce_edit();
     }
 
-    fn add_minimized(&self, user: &WriteSignal<User>) {
-        user.update(|usr| usr.minimized_notes.push(self.clone()));
+    fn is_overdue(&self, now: &TimeDate) -> bool {
+        self.due.get().is_some_and(|due| due <= *now)
     }
 
-    fn remove_minimized(&self, user: &WriteSignal<User>) {
+    fn reminder_is_due(&self, now: &TimeDate) -> bool {
+        !self.reminder_sent.get() && self.reminder.get().is_some_and(|reminder| reminder <= *now)
+    }
+
+    /// keeps existing label -> note mappings and resolves any new labels by title
+    fn resolve_links(&self, notes: &[Note]) {
+        let labels = wiki_link_labels(&self.body.get());
+        let previous = self.links.get();
+
+        let links = labels.into_iter()
+            .filter_map(|label| {
+                let target = previous.iter()
+                    .find(|link| link.label == label)
+                    .map(|link| link.target)
+                    .or_else(|| find_note_by_title(notes, &label, self.id).map(|note| note.id))?;
+
+                Some(NoteLink { label, target })
+            })
+            .collect::<Vec<_>>();
+
+        if links != previous {
+            self.links.set(links);
+        }
+    }
+
+    fn links_to(&self, target: Uuid) -> bool {
+        self.links.with(|links| links.iter().any(|link| link.target == target))
+    }
+
+    fn set_recurrence(&self, recurrence: Option<Recurrence>) {
+        self.recurrence.set(recurrence);
+        self.induce_edit();
+    }
+
+    /// recurring notes roll their due date (and reminder) forward, one off notes just lose their due date
+    fn check_off(&self) {
+        let Some(due) = self.due.get() else {
+            return;
+        };
+
+        match self.recurrence.get() {
+            Some(recurrence) => {
+                let next_due = recurrence.next_after(&due);
+                let shift = next_due.epoch_time - due.epoch_time;
+
+                self.due.set(Some(next_due));
+                self.reminder.update(|reminder| *reminder = reminder.as_ref().map(|reminder| reminder.offset_by(shift)));
+                self.reminder_sent.set(false);
+                self.items.update(|items| items.iter_mut().for_each(|item| item.done = false));
+            }
+            None => {
+                self.due.set(None);
+                self.reminder.set(None);
+            }
+        }
+
+        self.induce_edit();
+    }
+
+    fn add_attachment(&self, attachment: Attachment) {
+        self.attachments.update(|attachments| attachments.push(attachment));
+        self.induce_edit();
+    }
+
+    fn remove_attachment(&self, id: Uuid) {
+        self.attachments.update(|attachments| attachments.retain(|attachment| attachment.id != id));
+        self.induce_edit();
+    }
+
+    fn add_pinned(&self, user: &WriteSignal<User>) {
+        user.update(|usr| usr.pinned_notes.push(self.clone()));
+    }
+
+    fn remove_pinned(&self, user: &WriteSignal<User>) {
+        user.update(|usr| usr.pinned_notes.retain(|note| note != self))
+    }
+
+    #[allow(dead_code)]
+    fn add_minimized(&self, user: &WriteSignal<User>) {
+        user.update(|usr| usr.minimized_notes.push(self.clone()));
+    }
+
+    #[allow(dead_code)]
+    fn remove_minimized(&self, user: &WriteSignal<User>) {
         user.update(|usr| usr.minimized_notes.retain(|note| note != self));
     }
+
+    /// the owner always has write access, everyone else goes through the acl
+    fn access_level(&self, viewer: &User) -> Option<AccessLevel> {
+        if self.user.uuid == viewer.uuid {
+            return Some(AccessLevel::Write);
+        }
+
+        self.access.with(|acl| {
+            acl.iter()
+                .find(|entry| entry.user.uuid == viewer.uuid)
+                .map(|entry| entry.level)
+        })
+    }
+
+    #[inline]
+    fn can_view(&self, viewer: &User) -> bool {
+        self.access_level(viewer).is_some()
+    }
+
+    #[inline]
+    fn can_edit(&self, viewer: &User) -> bool {
+        self.access_level(viewer) == Some(AccessLevel::Write)
+    }
+
+    #[inline]
+    fn is_checklist(&self) -> bool {
+        self.kind.get() == NoteKind::Checklist
+    }
+
+    fn set_kind(&self, kind: NoteKind) {
+        self.kind.set(kind);
+        self.induce_edit();
+    }
+
+    fn add_item(&self, text: String) {
+        self.items.update(|items| items.push(ChecklistItem::new(text)));
+        self.induce_edit();
+    }
+
+    fn update_item(&self, id: Uuid, text: String) {
+        self.items.update(|items| {
+            if let Some(item) = items.iter_mut().find(|item| item.id == id) {
+                item.text = text;
+            }
+        });
+        self.induce_edit();
+    }
+
+    fn remove_item(&self, id: Uuid) {
+        self.items.update(|items| items.retain(|item| item.id != id));
+        self.induce_edit();
+    }
+
+    /// moves an item up (negative offset) or down (positive offset), clamped to the list bounds
+    fn move_item(&self, id: Uuid, offset: isize) {
+        self.items.update(|items| {
+            let Some(from) = items.iter().position(|item| item.id == id) else {
+                return;
+            };
+            let to = (from as isize + offset).clamp(0, items.len() as isize - 1) as usize;
+            let item = items.remove(from);
+            items.insert(to, item);
+        });
+        self.induce_edit();
+    }
+
+    fn toggle_item(&self, id: Uuid) {
+        self.items.update(|items| {
+            if let Some(item) = items.iter_mut().find(|item| item.id == id) {
+                item.done = !item.done;
+            }
+        });
+        self.induce_edit();
+    }
+
+    /// (done, total)
+    fn checklist_progress(&self) -> (usize, usize) {
+        self.items.with(|items| (items.iter().filter(|item| item.done).count(), items.len()))
+    }
+
+    /// comments are saved on their own rather than with the rest of the note, so
+    /// commenting doesn't need write access and can't clobber someone's edit
+    fn add_comment(&self, cx: Scope, author: &User, body: String) {
+        if !self.can_view(author) || author.is_guest() {
+            return;
+        }
+
+        let (note_id, comments) = (self.id, self.comments);
+        spawn_local(async move {
+            match add_comment(cx, note_id, body).await {
+                Ok(comment) => comments.update(|comments| comments.push(comment)),
+                Err(e) => log!("failed to add comment: {}", e),
+            }
+        });
+    }
+
+    /// only the author of a comment is allowed to change it
+    fn edit_comment(&self, cx: Scope, id: Uuid, body: String) {
+        let (note_id, comments) = (self.id, self.comments);
+        spawn_local(async move {
+            match edit_comment(cx, note_id, id, body).await {
+                Ok(edited) => comments.update(|comments| {
+                    if let Some(comment) = comments.iter_mut().find(|c| c.id == id) {
+                        *comment = edited;
+                    }
+                }),
+                Err(e) => log!("failed to edit comment: {}", e),
+            }
+        });
+    }
+
+    fn delete_comment(&self, cx: Scope, id: Uuid) {
+        let (note_id, comments) = (self.id, self.comments);
+        spawn_local(async move {
+            match delete_comment(cx, note_id, id).await {
+                Ok(()) => comments.update(|comments| comments.retain(|c| c.id != id)),
+                Err(e) => log!("failed to delete comment: {}", e),
+            }
+        });
+    }
 }
 
 #[component]
@@ -356,7 +1211,7 @@ fn PinButton(cx: Scope, toggle: WriteSignal<bool>) -> impl IntoView {
 }
 
 #[component]
-fn ImgBtn<T: Fn() -> () + 'static>(cx: Scope, link: &'static str, on_click: T) -> impl IntoView {
+fn ImgBtn<T: Fn() + 'static>(cx: Scope, link: &'static str, on_click: T) -> impl IntoView {
     let link = link.to_owned();
 
     view!{
@@ -382,7 +1237,10 @@ fn ViewNote(
     //
     //add a tool bar with the name of the app maybe
 
-    let pin_note = move || note.get().toggle_pinned();
+    let pin_note = move || {
+        note.get().toggle_pinned();
+        note.get().mirror_to_db(cx);
+    };
 
     let minimize_note = move || note.get().toggle_minimized();
 
@@ -394,13 +1252,71 @@ fn ViewNote(
 
     let hide_note = move || note.get().hide(&viewer.write_only());
 
-    let delete_note = move || note.get().delete(&write_notes);
+    let delete_note = move || note.get().delete(cx, &write_notes);
     
-    let check_for_edit_perms = move || if note.get().user.uuid == viewer.get().uuid {note.get().is_editing.set(true)};
+    let check_for_edit_perms = move || if note.get().can_edit(&viewer.get()) {note.get().is_editing.set(true)};
+
+    let (show_share, set_show_share) = create_signal(cx, false);
+
+    let templates = expect_context::<RwSignal<Vec<NoteTemplate>>>(cx);
+
+    let save_as_template = move || {
+        let template = NoteTemplate::from_note(&note.get(), &viewer.get());
+        templates.update(|templates| templates.push(template.clone()));
+
+        spawn_local(async move {
+            match save_template(cx, template).await {
+                Ok(Ok(())) => {}
+                Ok(Err(invalid)) => log!("template not saved, {}", invalid),
+                Err(e) => log!("failed to save template: {}", e),
+            }
+        });
+    };
+
+    let notes = expect_context::<ReadSignal<Vec<Note>>>(cx);
+    let now = expect_context::<Clock>(cx).0;
+
+    let open_linked_note = move |target: Uuid| {
+        let Some(target) = notes.with(|notes| notes.iter().find(|n| n.id == target).cloned()) else {
+            return;
+        };
+
+        target.minimized.set(false);
+        target.maximized.set(true);
+        focused_view_note.get().map(|prev_focused| (prev_focused != target).then(|| prev_focused.unmaximize()));
+        focused_view_note.set(Some(target));
+    };
+
+    //wiki links render as `#note-<uuid>` anchors, the browser handles the scrolling
+    let follow_wiki_link = move |e: ev::MouseEvent| {
+        let Some(anchor) = e.target()
+            .and_then(|target| wasm_bindgen::JsCast::dyn_into::<web_sys::Element>(target).ok())
+            .and_then(|element| element.closest("a").ok().flatten()) else {
+            return;
+        };
+
+        let target = anchor.get_attribute("href")
+            .and_then(|href| href.strip_prefix("#note-").and_then(|id| Uuid::parse_str(id).ok()));
+
+        if let Some(target) = target {
+            open_linked_note(target);
+        }
+    };
+
+    let backlinks = move || {
+        let id = note.get().id;
+        let viewer = viewer.get();
+        notes.with(|notes| {
+            notes.iter()
+                .filter(|other| other.id != id && other.can_view(&viewer) && other.links_to(id))
+                .map(|other| (other.id, other.title.get()))
+                .collect::<Vec<_>>()
+        })
+    };
 
     create_effect(cx, move |_| {
         if note.get().pinned.get() {
-            /**
+            /*
             write_notes.update(|notes| {
                 let old_index = notes.iter().position(|n| n.id == note.get_untracked().id).unwrap();
                 let mut index = old_index.clone();
@@ -424,7 +1340,7 @@ fn ViewNote(
                     notes.swap(old_index, index);
                 }
             });
-            **/
+            */
             note.get().add_pinned(&viewer.write_only());
         }else{
             note.get().remove_pinned(&viewer.write_only());
@@ -436,34 +1352,82 @@ fn ViewNote(
         <div class="flex_column">
             //{move || (note.get().pinned.get()).then_some(view!{cx, <PinButton toggle=note.get().pinned.write_only()/>})}
 
-        {move || (note.get().pinned.get().then_some(view!{cx, 
+        {move || note.get().pinned.get().then_some(view!{cx, 
             <div class="pinned_fold" style:background-color=move || format!("5px 5px color-mix(in srgb, {} 50%, rgba(0, 0, 0, .75))", note.get().colour)>
                 <div class="pinned_mask"/>
             </div>
-        }))}
+        })}
 
 
             <div class="flex_seperator bottom_border">
-                <ImgBtn link="https://cdn-icons-png.flaticon.com/512/2672/2672101.png" on_click= move || pin_note()/>
+                <ImgBtn link="https://cdn-icons-png.flaticon.com/512/2672/2672101.png" on_click=pin_note/>
                 {move || (note.get().minimized.get()).then_some(view!{cx, <h2 class="minimized_title_repr">{note.get().title.get()}</h2>})}
+                {move || (note.get().minimized.get() && note.get().is_checklist()).then(|| {
+                    let (done, total) = note.get().checklist_progress();
+                    view!{cx, <h1 class="reset small_details">{format!("{}/{} done", done, total)}</h1>}
+                })}
+                {move || {
+                    let count = note.get().comments.with(Vec::len);
+                    (note.get().minimized.get() && count > 0).then_some(view!{cx, <span class="comment_badge">{count}</span>})
+                }}
                 <div>
-                    <ImgBtn link="https://cdn-icons-png.flaticon.com/512/3484/3484290.png" on_click= move || minimize_note()/>
-                    <ImgBtn link="https://cdn-icons-png.flaticon.com/512/2901/2901214.png" on_click= move || maximize_note()/>
-                    {move || (!note.get().pinned.get() && note.get().user.uuid != viewer.get().uuid).then_some(view!{cx, <ImgBtn link="https://cdn-icons-png.flaticon.com/512/876/876769.png" on_click= move || hide_note()/>})}
-                    {move || (note.get().user == viewer.get()).then_some(view!{cx, <ImgBtn link="https://cdn-icons-png.flaticon.com/512/3096/3096673.png" on_click=move || delete_note()/>})}
+                    <ImgBtn link="https://cdn-icons-png.flaticon.com/512/3484/3484290.png" on_click=minimize_note/>
+                    <ImgBtn link="https://cdn-icons-png.flaticon.com/512/2901/2901214.png" on_click=maximize_note/>
+                    {move || (!note.get().pinned.get() && note.get().user.uuid != viewer.get().uuid).then_some(view!{cx, <ImgBtn link="https://cdn-icons-png.flaticon.com/512/876/876769.png" on_click=hide_note/>})}
+                    {move || (note.get().user == viewer.get()).then_some(view!{cx, <ImgBtn link="https://cdn-icons-png.flaticon.com/512/3096/3096673.png" on_click=delete_note/>})}
+                    {move || (note.get().user == viewer.get()).then_some(view!{cx, <button class="reset share_btn" on:click=move |_| set_show_share.update(|show| *show = !*show)> "share" </button>})}
+                    {move || (note.get().user == viewer.get()).then_some(view!{cx, <button class="reset share_btn" on:click=move |_| save_as_template()> "save as template" </button>})}
                 </div>
             </div>
 
+            {move || show_share.get().then_some(view!{cx, <ShareDialog note/>})}
+
             <div class="flex_seperator text_padding">
                 <h1 class="reset small_details"> {note.get().date.format_datetime()} </h1>
                 <h1 class="reset small_details">"@"{note.get().user.username}</h1>
             </div>
 
+            {move || (note.get().due.get().is_some() || note.get().reminder.get().is_some()).then(|| view!{
+                cx,
+                <div class="flex_seperator text_padding">
+                    {move || note.get().due.get().map(|due| view!{cx, <h1 class="reset small_details" class:overdue=move || note.get().is_overdue(&now.get())>"due "{due.format_datetime()}</h1>})}
+                    {move || note.get().reminder.get().map(|reminder| view!{cx, <h1 class="reset small_details">"reminder "{reminder.format_datetime()}</h1>})}
+                    {move || note.get().recurrence.get().map(|recurrence| view!{cx, <h1 class="reset small_details">"repeats "{recurrence.describe()}</h1>})}
+                    //checking off only means something for notes with a deadline
+                    {move || (note.get().due.get().is_some() && note.get().can_edit(&viewer.get())).then(|| view!{cx, <button class="reset share_btn" on:click=move |_| {
+                        note.get().check_off();
+                        note.get().mirror_to_db(cx);
+                    }> "done" </button>})}
+                </div>
+            })}
+
             {move || (!note.get().minimized.get()).then_some(view!{cx, 
                 <div class="text_bounding_area" on:dblclick=move |_| check_for_edit_perms()>
                     <h1 class="note_title"> {note.get().title} </h1>
 
-                    <p class="note_text_body"> {note.get().body} </p>
+                    {move || note.get().tags.get().into_iter().map(|tag| view!{cx, <span class="reset small_details note_tag">"#"{tag}</span>}).collect_view(cx)}
+
+                    {move || if note.get().is_checklist() {
+                        view!{cx, <ChecklistView note viewer/>}.into_view(cx)
+                    }else{
+                        view!{cx, <div class="note_text_body markdown" on:click=follow_wiki_link inner_html=move || notes.with(|notes| render_markdown(&render_wiki_links(&note.get(), notes)))/>}.into_view(cx)
+                    }}
+
+                    {move || {
+                        let backlinks = backlinks();
+                        (!backlinks.is_empty()).then(|| view!{
+                            cx,
+                            <div class="backlinks">
+                                <h1 class="reset small_details">"linked from"</h1>
+                                {backlinks.into_iter().map(|(id, title)| {
+                                    let title = if title.is_empty() { "untitled note".to_owned() } else { title };
+                                    view!{cx, <a class="reset small_details" href=format!("#note-{}", id) on:click=move |_| open_linked_note(id)>{title}</a>}
+                                }).collect_view(cx)}
+                            </div>
+                        })
+                    }}
+
+                    <AttachmentList note editable=false/>
 
                     {move || (note.get().last_edit.get().is_some()).then(|| {
                         let datetime = note.get().last_edit.get().unwrap();
@@ -471,6 +1435,8 @@ fn ViewNote(
                     })}
                 </div>
             })}
+
+            {move || (!note.get().minimized.get()).then_some(view!{cx, <CommentThread note viewer/>})}
         </div>
     };
 
@@ -484,371 +1450,1723 @@ fn ViewNote(
                 </div>
             }
         }else{
-            view!{
-                cx,
-                {note_body.clone()}
-            }
+            note_body.clone()
         }}
     }
 }
 
-fn filter_input(inp: String) -> String {
-    if inp.is_empty() {
-        return inp;
-    }
-
-    inp.trim().to_owned()
-}
+/// lists everyone a note is shared with and lets the owner add or remove people
+#[component]
+fn ShareDialog(cx: Scope, note: ReadSignal<Note>) -> impl IntoView {
+    let (username, set_username) = create_signal(cx, String::new());
+    let (level, set_level) = create_signal(cx, AccessLevel::Read);
+    let (error, set_error) = create_signal(cx, None::<String>);
+    let (suggestions, set_suggestions) = create_signal(cx, Vec::<SerializedUser>::new());
+
+    //asks the server rather than only suggesting people already seen on loaded notes
+    let lookup = move |prefix: String| {
+        set_username.set(prefix.clone());
+        spawn_local(async move {
+            match find_users(cx, prefix).await {
+                Ok(users) => set_suggestions.set(users),
+                Err(e) => log!("failed to look up users: {}", e),
+            }
+        });
+    };
 
-fn parse_text_event_value(e: ev::Event) -> String {
-    event_target_value(&e)
-}
+    let datalist_id = format!("share_users_{}", note.get().id);
 
+    //the access list only changes once the server has it, so nothing can sit unsaved in the browser
+    let share = move || {
+        let note = note.get();
+        let (name, level) = (filter_input(username.get()), level.get());
 
-#[component]
-fn EditNoteDisplay(cx: Scope, note: ReadSignal<Note>, viewer: RwSignal<User>, edit_focused_note: RwSignal<Option<Note>>, focused_view_note: RwSignal<Option<Note>>) -> impl IntoView {
-    let update_title = move |e: ev::Event| {
-        let str = filter_input(parse_text_event_value(e));
-        note.get().update_title(str);
+        spawn_local(async move {
+            match share_note(cx, note.id, name, level).await {
+                Ok(access) => {
+                    note.access.set(access);
+                    set_username.set(String::new());
+                    set_error.set(None);
+                }
+                Err(e) => set_error.set(Some(e.to_string())),
+            }
+        });
     };
 
-    let update_body = move |e: ev::Event| {
-        let str = filter_input(parse_text_event_value(e));
-        note.get().update_body(str);
+    let unshare = move |user: Uuid| {
+        let note = note.get();
+        spawn_local(async move {
+            match unshare_note(cx, note.id, user).await {
+                Ok(access) => note.access.set(access),
+                Err(e) => set_error.set(Some(e.to_string())),
+            }
+        });
     };
-    
-    let body_update_notifier = create_rw_signal(cx, false);
-    let title_update_notifier = create_rw_signal(cx, false);
 
-    let (reached_title_limit, set_reached_title_limit) = create_signal(cx, note.get().body.get().len() as u64 > Note::TITLE_CHAR_LIMIT);
-    let (reached_body_limit, set_reached_body_limit) = create_signal(cx, note.get().body.get().len() as u64 > Note::BODY_CHAR_LIMIT);
+    view!{
+        cx,
+        <div class="share_dialog">
+            <div class="flex_seperator">
+                <input type="text" class="reset bottom_border single_line_text_input" placeholder="username" list=datalist_id.clone() prop:value=move || username.get() on:input=move |e| lookup(event_target_value(&e))/>
+                <datalist id=datalist_id>
+                    <For
+                        each=move || suggestions.get()
+                        key=|user| user.uuid
+                        view=move |cx, user: SerializedUser| view!{cx, <option value=user.username.clone()/>}
+                    />
+                </datalist>
+                <select on:change=move |e| set_level.set(AccessLevel::from_label(&event_target_value(&e)))>
+                    <option value="read" selected=move || level.get() == AccessLevel::Read>"read"</option>
+                    <option value="write" selected=move || level.get() == AccessLevel::Write>"write"</option>
+                </select>
+                <button class="reset share_btn" on:click=move |_| share()> "add" </button>
+            </div>
 
-    create_effect(cx, move |_| {
-        if !body_update_notifier.get() && !title_update_notifier.get() {
-            note.get().mirror_to_db()
-        }
-    });
+            {move || error.get().map(|error| view!{cx, <h1 class="reset small_details">{error}</h1>})}
+
+            <PublicLink note/>
+
+            <For
+                each=move || note.get().access.get()
+                key=|entry| (entry.user.uuid, entry.level)
+                view=move |cx, entry: NoteAccess| {
+                    let uuid = entry.user.uuid;
+                    view!{
+                        cx,
+                        <div class="flex_seperator text_padding">
+                            <h1 class="reset small_details">"@"{entry.user.username.clone()}" ("{entry.level.label()}")"</h1>
+                            <button class="reset share_btn" on:click=move |_| unshare(uuid)> "remove" </button>
+                        </div>
+                    }
+                }
+            />
+        </div>
+    }
+}
 
-    let notifier_body = view!{
+/// checkboxes can be ticked straight from the view, no need to go into edit mode
+#[component]
+fn ChecklistView(cx: Scope, note: ReadSignal<Note>, viewer: RwSignal<User>) -> impl IntoView {
+    let can_toggle = move || note.get().can_edit(&viewer.get());
+
+    view!{
         cx,
-        <CharacterLimit limit=Note::BODY_CHAR_LIMIT text=note.get().body reached_limit=set_reached_body_limit/>
-    };
+        <ul class="reset checklist">
+            <For
+                each=move || note.get().items.get()
+                key=|item| (item.id, item.done, item.text.clone())
+                view=move |cx, item: ChecklistItem| {
+                    let id = item.id;
+                    view!{
+                        cx,
+                        <li class="checklist_item" class:checklist_done=item.done>
+                            <input type="checkbox" prop:checked=item.done prop:disabled=move || !can_toggle() on:change=move |_| {
+                                note.get().toggle_item(id);
+                                note.get().mirror_to_db(cx);
+                            }/>
+                            <span>{item.text.clone()}</span>
+                        </li>
+                    }
+                }
+            />
+        </ul>
+    }
+}
 
-    //TOOD: css animations and stuff
+#[component]
+fn ChecklistEdit(cx: Scope, note: ReadSignal<Note>) -> impl IntoView {
+    let (new_item, set_new_item) = create_signal(cx, String::new());
+
+    let add_item = move || {
+        let text = filter_input(new_item.get());
+        if !text.is_empty() {
+            note.get().add_item(text);
+            set_new_item.set(String::new());
+        }
+    };
 
     view!{
         cx,
-        <div>
-            <h1 class="text_edit_title"> "title" </h1>
-            <input class="reset bottom_border single_line_text_input" type="text" value=note.get().title on:change=move |e| update_title(e)/>
-
-            //{move || if title_update_notifier.get() {
-                //Some(view!{
-                    //cx, 
-                    //{notifier_title.clone()}
-                //})
-            //}else{
-                //None
-            //}}
-            <div>
-                <h1 class="text_edit_body">"body" </h1>
-                <textarea class="reset bottom_border multi_line_text_input" value=note.get().body on:change= move |e| update_body(e)  />
-                //{move || if body_update_notifier.get() {
-                    //Some(view!{
-                        //cx, 
-                        //{notifier_body.clone()}
-                    //})
-                //}else{
-                    //None
-                //}}
+        <div class="flex_column">
+            <For
+                each=move || note.get().items.get()
+                key=|item| item.id
+                view=move |cx, item: ChecklistItem| {
+                    let id = item.id;
+                    view!{
+                        cx,
+                        <div class="flex_seperator checklist_item">
+                            <input type="text" class="reset bottom_border single_line_text_input" value=item.text.clone() on:change=move |e| note.get().update_item(id, filter_input(event_target_value(&e)))/>
+                            <div>
+                                <button class="reset share_btn" on:click=move |_| note.get().move_item(id, -1)> "up" </button>
+                                <button class="reset share_btn" on:click=move |_| note.get().move_item(id, 1)> "down" </button>
+                                <button class="reset share_btn" on:click=move |_| note.get().remove_item(id)> "remove" </button>
+                            </div>
+                        </div>
+                    }
+                }
+            />
+            <div class="flex_seperator">
+                <input type="text" class="reset bottom_border single_line_text_input" placeholder="new item..." prop:value=move || new_item.get() on:input=move |e| set_new_item.set(event_target_value(&e)) on:keydown=move |e: ev::KeyboardEvent| if e.key() == "Enter" { add_item() }/>
+                <button class="reset share_btn" on:click=move |_| add_item()> "add" </button>
             </div>
         </div>
-    };
+    }
 }
 
 #[component]
-fn NoteEdit(cx: Scope, note: ReadSignal<Note>, viewer: RwSignal<User>, edit_focused_note: RwSignal<Option<Note>>, focused_view_note: RwSignal<Option<Note>>, write_notes: WriteSignal<Vec<Note>>) -> impl IntoView {
-    let body_update_notifier = create_rw_signal(cx, false);
-    let title_update_notifier = create_rw_signal(cx, false);
-
-    let update_title = move |e: ev::Event| {
-        let str = filter_input(parse_text_event_value(e));
-        note.get().update_title(str);
+fn RecurrenceEdit(cx: Scope, note: ReadSignal<Note>) -> impl IntoView {
+    let kind = move || match note.get().recurrence.get() {
+        None => "none",
+        Some(Recurrence::Daily) => "daily",
+        Some(Recurrence::Weekdays) => "weekdays",
+        Some(Recurrence::Weekly(_)) => "weekly",
+        Some(Recurrence::MonthlyOnDay(_)) => "monthly",
+        Some(Recurrence::Every { .. }) => "every",
     };
 
-    let update_body = move |e: ev::Event| {
-        body_update_notifier.set(true);
-        let str = filter_input(parse_text_event_value(e.into()));
-        note.get().update_body(str);
-    };
+    let due_date = move || note.get().due.get().map(|due| due.to_local().date_naive()).unwrap_or_default();
 
-    let update_title_from_keypress = move |e: ev::KeyboardEvent| {
-        if e.key() == "Enter" {
-            note.get().is_editing.set(false);
-        }else{
-            update_title(e.into())
-        }
+    let set_kind = move |e: ev::Event| {
+        let recurrence = match event_target_value(&e).as_str() {
+            "daily" => Some(Recurrence::Daily),
+            "weekdays" => Some(Recurrence::Weekdays),
+            "weekly" => Some(Recurrence::Weekly(vec![due_date().weekday().num_days_from_monday()])),
+            "monthly" => Some(Recurrence::MonthlyOnDay(due_date().day())),
+            "every" => Some(Recurrence::Every { interval: 2, unit: RecurrenceUnit::Weeks, day: Some(due_date().day()) }),
+            _ => None,
+        };
+        note.get().set_recurrence(recurrence);
     };
 
-    let update_body_from_keypress = move |e: ev::KeyboardEvent| {
-        if e.key() == "Enter" {
-            note.get().is_editing.set(false);
-        }else{
-            update_body(e.into())
+    let toggle_weekday = move |day: u32| {
+        if let Some(Recurrence::Weekly(mut days)) = note.get().recurrence.get() {
+            match days.iter().position(|d| *d == day) {
+                Some(index) => { days.remove(index); }
+                None => { days.push(day); days.sort(); }
+            }
+            note.get().set_recurrence(Some(Recurrence::Weekly(days)));
         }
     };
 
-    let (reached_body_limit, set_reached_body_limit) = create_signal(cx, note.get().body.get().len() as u64 > Note::BODY_CHAR_LIMIT);
-    let (reached_title_limit, set_reached_title_limit) = create_signal(cx, note.get().title.get().len() as u64 > Note::TITLE_CHAR_LIMIT);
-
-
-    let input_ref = create_node_ref::<Input>(cx);
+    view!{
+        cx,
+        <div class="flex_seperator">
+            <h1 class="reset small_details">
+                "repeat "
+                <select on:change=set_kind>
+                    {["none", "daily", "weekdays", "weekly", "monthly", "every"].into_iter().map(|option| view!{
+                        cx,
+                        <option value=option selected=move || kind() == option>{option}</option>
+                    }).collect_view(cx)}
+                </select>
+            </h1>
+
+            {move || match note.get().recurrence.get() {
+                Some(Recurrence::Weekly(days)) => WEEKDAY_NAMES.iter().enumerate().map(|(day, name)| {
+                    let day = day as u32;
+                    view!{
+                        cx,
+                        <label class="reset small_details">
+                            {*name}
+                            <input type="checkbox" prop:checked=days.contains(&day) on:change=move |_| toggle_weekday(day)/>
+                        </label>
+                    }
+                }).collect_view(cx),
+                Some(Recurrence::MonthlyOnDay(day)) => view!{
+                    cx,
+                    <input type="number" min="1" max="31" value=day on:change=move |e| {
+                        let day = event_target_value(&e).parse().unwrap_or(1u32).clamp(1, 31);
+                        note.get().set_recurrence(Some(Recurrence::MonthlyOnDay(day)));
+                    }/>
+                }.into_view(cx),
+                Some(Recurrence::Every { interval, unit, day }) => view!{
+                    cx,
+                    <div>
+                        <input type="number" min="1" value=interval on:change=move |e| {
+                            let interval = event_target_value(&e).parse().unwrap_or(1u32).max(1);
+                            note.get().set_recurrence(Some(Recurrence::Every { interval, unit, day }));
+                        }/>
+                        <select on:change=move |e| {
+                            let unit = match event_target_value(&e).as_str() {
+                                "days" => RecurrenceUnit::Days,
+                                "months" => RecurrenceUnit::Months,
+                                _ => RecurrenceUnit::Weeks,
+                            };
+                            note.get().set_recurrence(Some(Recurrence::Every { interval, unit, day }));
+                        }>
+                            <option value="days" selected=unit == RecurrenceUnit::Days>"days"</option>
+                            <option value="weeks" selected=unit == RecurrenceUnit::Weeks>"weeks"</option>
+                            <option value="months" selected=unit == RecurrenceUnit::Months>"months"</option>
+                        </select>
+                    </div>
+                }.into_view(cx),
+                _ => ().into_view(cx),
+            }}
+        </div>
+    }
+}
 
+/// thumbnails for image attachments and plain links for everything else
+#[component]
+fn AttachmentList(cx: Scope, note: ReadSignal<Note>, editable: bool) -> impl IntoView {
+    view!{
+        cx,
+        <div class="attachments">
+            <For
+                each=move || note.get().attachments.get()
+                key=|attachment| attachment.id
+                view=move |cx, attachment: Attachment| {
+                    let id = attachment.id;
+                    let url = attachment.url();
+                    view!{
+                        cx,
+                        <div class="attachment">
+                            {if attachment.is_image() {
+                                view!{cx, <a href=url.clone() target="_blank"><img class="attachment_thumbnail" src=attachment.thumbnail_url() alt=attachment.name.clone() loading="lazy"/></a>}.into_view(cx)
+                            }else{
+                                view!{cx, <a class="reset small_details" href=url.clone() target="_blank">{attachment.name.clone()}" ("{attachment.format_size()}")"</a>}.into_view(cx)
+                            }}
+                            {editable.then(|| view!{cx, <button class="reset share_btn" on:click=move |_| note.get().remove_attachment(id)> "remove" </button>})}
+                        </div>
+                    }
+                }
+            />
+        </div>
+    }
+}
 
-    create_effect(cx, move |_| {
-        if let Some(input) = input_ref.get() {
-            request_animation_frame(move || {
-                let _ = input.focus();
-            })
-        }
-    });
+#[component]
+fn AttachmentUpload(cx: Scope, note: ReadSignal<Note>) -> impl IntoView {
+    let (error, set_error) = create_signal(cx, None::<String>);
+    let (uploading, set_uploading) = create_signal(cx, 0usize);
+
+    let upload = move |e: ev::Event| {
+        let input = event_target::<web_sys::HtmlInputElement>(&e);
+        let Some(files) = input.files() else {
+            return;
+        };
 
+        set_error.set(None);
 
-    //let body_timeout_handle = create_rw_signal(cx, None);
-    //let title_timeout_handle = create_rw_signal(cx, None);
+        for index in 0..files.length() {
+            let Some(file) = files.get(index) else {
+                continue;
+            };
 
-    let notifier_body = view!{
-        cx,
-        <CharacterLimit limit=Note::BODY_CHAR_LIMIT text=note.get().body reached_limit=set_reached_body_limit/>
-    };
+            set_uploading.update(|count| *count += 1);
+            spawn_local(async move {
+                match upload_attachment(file).await {
+                    Ok(attachment) => note.get_untracked().add_attachment(attachment),
+                    Err(e) => set_error.set(Some(e)),
+                }
+                set_uploading.update(|count| *count -= 1);
+            });
+        }
 
-        let notifier_title = view!{
-        cx,
-        <CharacterLimit limit=Note::TITLE_CHAR_LIMIT text = note.get().title reached_limit=set_reached_title_limit/>
+        input.set_value("");
     };
 
     view!{
         cx,
         <div>
-            <h1 class="text_edit_title"> "title" </h1>
-            <input type="text" class="reset bottom_border single_line_text_input" node_ref=input_ref placeholder=note.get().title value=note.get().title on:keydown=move |e| update_title_from_keypress(e.into()) on:input= move |e| update_title(e)/>
-            //{move || title_update_notifier.get().then_some(view!{cx, <TimeoutNotifier body=notifier_title.clone() show_state=body_update_notifier timeout_duration=2000 timeout_handler=title_timeout_handle/>})}
-
-            <div>
-                <h1 class="text_edit_body"> "body" </h1>
-                <textarea class="reset bottom_border multi_line_text_input" value=note.get().body placeholder=note.get().body on:keydown= move |e| update_body_from_keypress(e.into()) on:input= move |e| update_body(e)  />
-
-                //{move || body_update_notifier.get().then_some(view!{cx, <TimeoutNotifier body=notifier_body.clone() show_state=body_update_notifier timeout_duration=2000 timeout_handler=body_timeout_handle/>})}
-            </div>
+            <h1 class="text_edit_body"> "attachments" </h1>
+            <AttachmentList note editable=true/>
+            <input type="file" multiple on:change=upload/>
+            {move || (uploading.get() > 0).then(|| view!{cx, <h1 class="reset small_details">"uploading..."</h1>})}
+            {move || error.get().map(|error| view!{cx, <h1 class="reset small_details">{error}</h1>})}
         </div>
     }
 }
 
-
+/// expandable list of comments under a note, anyone who can see the note can join in
 #[component]
-fn TimeoutNotifier(cx: Scope, body: View, show_state: RwSignal<bool>, timeout_duration: u64, timeout_handler: RwSignal<Option<TimeoutHandle>>) -> impl IntoView {
-    create_effect(cx, move |_| {
-        if show_state.get() {
-            match timeout_handler.get() {
-                Some(timeout) => {
-                    show_state.set(true);
-                    timeout.clear();
-
-                    let new_timeout = Some(set_timeout_with_handle(move || {
-                        show_state.set(false);
-                        timeout_handler.set(None)
-                    }, core::time::Duration::from_millis(timeout_duration)).unwrap());
-
-                    timeout_handler.set(new_timeout);
-                }
-                _ => {
-                    show_state.set(true);
+fn CommentThread(cx: Scope, note: ReadSignal<Note>, viewer: RwSignal<User>) -> impl IntoView {
+    let (expanded, set_expanded) = create_signal(cx, false);
+    let (draft, set_draft) = create_signal(cx, String::new());
 
-                    let new_timeout = Some(set_timeout_with_handle(move || {
-                        show_state.set(false);
-                        timeout_handler.set(None)
-                    }, core::time::Duration::from_millis(timeout_duration)).unwrap());
+    let count = move || note.get().comments.with(Vec::len);
 
-                    timeout_handler.set(new_timeout);
-                }
-            }
+    let post = move || {
+        let body = filter_input(draft.get());
+        if body.is_empty() {
+            return;
         }
-    });
+
+        note.get().add_comment(cx, &viewer.get(), body);
+        set_draft.set(String::new());
+    };
 
     view!{
-        cx, 
-        {move || (show_state.get()).then_some(view!{cx,  {body.clone()}})}
+        cx,
+        <div class="comment_thread">
+            <button class="reset small_details" on:click=move |_| set_expanded.update(|expanded| *expanded = !*expanded)>
+                {move || match count() {
+                    1 => "1 comment".to_owned(),
+                    n => format!("{} comments", n),
+                }}
+            </button>
+
+            {move || expanded.get().then(|| view!{
+                cx,
+                <div class="flex_column">
+                    <For
+                        each=move || note.get().comments.get()
+                        key=|comment| (comment.id, comment.last_edit.clone())
+                        view=move |cx, comment: Comment| view!{cx, <CommentView note viewer comment/>}
+                    />
+
+                    {move || (!viewer.get().is_guest()).then(|| view!{
+                        cx,
+                        <div class="flex_seperator">
+                            <textarea class="reset bottom_border multi_line_text_input" placeholder="add a comment..." prop:value=move || draft.get() on:input=move |e| set_draft.set(event_target_value(&e))/>
+                            <button class="reset share_btn" on:click=move |_| post()> "post" </button>
+                        </div>
+                    })}
+                </div>
+            })}
+        </div>
     }
 }
 
-pub enum TextColorState {
-    Normal,
-    Warning,
-    Limit,
-    OverLimit
-}
+#[component]
+fn CommentView(cx: Scope, note: ReadSignal<Note>, viewer: RwSignal<User>, comment: Comment) -> impl IntoView {
+    let (is_editing, set_is_editing) = create_signal(cx, false);
+    let (draft, set_draft) = create_signal(cx, comment.body.clone());
 
-impl ToString for TextColorState {
-    fn to_string(&self) -> String {
-        match *self {
-            Self::Normal => "white".to_owned(),
-            Self::Warning => "yellow".to_owned(),
-            Self::Limit => "red".to_owned(),
-            Self::OverLimit => "really red".to_owned(),
+    let id = comment.id;
+    let is_author = move || viewer.get().uuid == comment.author.uuid;
+
+    let save = move || {
+        let body = filter_input(draft.get());
+        if !body.is_empty() {
+            note.get().edit_comment(cx, id, body);
+        }
+        set_is_editing.set(false);
+    };
+
+    let body = comment.body.clone();
+
+    view!{
+        cx,
+        <div class="comment">
+            <div class="flex_seperator text_padding">
+                <h1 class="reset small_details">"@"{comment.author.username.clone()}</h1>
+                <h1 class="reset small_details">
+                    {comment.date.format_datetime()}
+                    {comment.last_edit.clone().map(|datetime| format!(" (edited {})", datetime.format_datetime()))}
+                </h1>
+            </div>
+
+            {move || if is_editing.get() {
+                view!{
+                    cx,
+                    <div class="flex_seperator">
+                        <textarea class="reset bottom_border multi_line_text_input" prop:value=move || draft.get() on:input=move |e| set_draft.set(event_target_value(&e))/>
+                        <button class="reset share_btn" on:click=move |_| save()> "save" </button>
+                    </div>
+                }.into_view(cx)
+            }else{
+                view!{cx, <p class="note_text_body">{body.clone()}</p>}.into_view(cx)
+            }}
+
+            {move || (is_author() && !is_editing.get()).then(|| view!{
+                cx,
+                <div>
+                    <button class="reset share_btn" on:click=move |_| set_is_editing.set(true)> "edit" </button>
+                    <button class="reset share_btn" on:click=move |_| note.get().delete_comment(cx, id)> "delete" </button>
+                </div>
+            })}
+        </div>
+    }
+}
+
+/// lets the owner create or revoke the public read only link for a note
+#[component]
+fn PublicLink(cx: Scope, note: ReadSignal<Note>) -> impl IntoView {
+    //0 means the link never expires
+    let (expiry_days, set_expiry_days) = create_signal(cx, 0i64);
+
+    let create_link = move || {
+        let note = note.get();
+        let expires = (expiry_days.get() > 0).then(|| TimeDate::days_from_now(expiry_days.get()));
+
+        spawn_local(async move {
+            match create_share_link(cx, note.id, expires).await {
+                Ok(link) => note.share_link.set(Some(link)),
+                Err(e) => log!("failed to create share link: {}", e),
+            }
+        });
+    };
+
+    let revoke_link = move || {
+        let note = note.get();
+        let Some(link) = note.share_link.get() else {
+            return;
+        };
+
+        spawn_local(async move {
+            match revoke_share_link(cx, link.token).await {
+                Ok(()) => note.share_link.set(None),
+                Err(e) => log!("failed to revoke share link: {}", e),
+            }
+        });
+    };
+
+    //the feed is opt in per note, a link someone was sent shouldn't end up in it by itself
+    let set_feed = move |in_feed: bool| {
+        let note = note.get();
+        spawn_local(async move {
+            match set_in_feed(cx, note.id, in_feed).await {
+                Ok(()) => note.in_feed.set(in_feed),
+                Err(e) => log!("failed to change the feed: {}", e),
+            }
+        });
+    };
+
+    view!{
+        cx,
+        <div class="flex_seperator text_padding">
+            {move || match note.get().share_link.get() {
+                Some(link) => view!{
+                    cx,
+                    <div class="flex_seperator">
+                        <a class="reset small_details" href=link.path()>{link.path()}</a>
+                        {link.expires.clone().map(|expires| view!{cx, <h1 class="reset small_details">"expires "{expires.format_date()}</h1>})}
+                        <button class="reset share_btn" on:click=move |_| revoke_link()> "revoke" </button>
+                        <h1 class="reset small_details">
+                            "list in my public feed "
+                            <input type="checkbox" prop:checked=move || note.get().in_feed.get() on:change=move |e| set_feed(event_target_checked(&e))/>
+                        </h1>
+                    </div>
+                }.into_view(cx),
+                None => view!{
+                    cx,
+                    <div class="flex_seperator">
+                        <select on:change=move |e| set_expiry_days.set(event_target_value(&e).parse().unwrap_or(0))>
+                            <option value="0">"never expires"</option>
+                            <option value="1">"1 day"</option>
+                            <option value="7">"7 days"</option>
+                            <option value="30">"30 days"</option>
+                        </select>
+                        <button class="reset share_btn" on:click=move |_| create_link()> "create public link" </button>
+                    </div>
+                }.into_view(cx),
+            }}
+        </div>
+    }
+}
+
+fn request_notification_permission() {
+    if web_sys::Notification::permission() == web_sys::NotificationPermission::Default {
+        let _ = web_sys::Notification::request_permission();
+    }
+}
+
+/// the iana name of the browser's time zone, e.g. `Europe/London`
+fn browser_timezone() -> String {
+    let options = js_sys::Intl::DateTimeFormat::new(&js_sys::Array::new(), &js_sys::Object::new()).resolved_options();
+    js_sys::Reflect::get(&options, &"timeZone".into())
+        .ok()
+        .and_then(|timezone| timezone.as_string())
+        .unwrap_or_else(|| "UTC".to_owned())
+}
+
+fn send_browser_notification(title: &str, body: &str) {
+    if web_sys::Notification::permission() != web_sys::NotificationPermission::Granted {
+        return;
+    }
+
+    let options = web_sys::NotificationOptions::new();
+    options.set_body(body);
+    let _ = web_sys::Notification::new_with_options(title, &options);
+}
+
+fn find_note_by_title<'a>(notes: &'a [Note], title: &str, exclude: Uuid) -> Option<&'a Note> {
+    notes.iter().find(|note| note.id != exclude && note.title.get().eq_ignore_ascii_case(title))
+}
+
+/// swaps every `[[label]]` for a markdown link to the target note, showing the target's
+/// current title. labels that don't point anywhere are left as they were written
+fn render_wiki_links(note: &Note, notes: &[Note]) -> String {
+    let links = note.links.get();
+    let body = note.body.get();
+    let mut rendered = String::with_capacity(body.len());
+    let mut written = 0;
+
+    for (span, label) in wiki_links(&body) {
+        let target = links.iter()
+            .find(|link| link.label == label)
+            .and_then(|link| notes.iter().find(|n| n.id == link.target))
+            .or_else(|| find_note_by_title(notes, &label, note.id));
+
+        if let Some(target) = target {
+            let title = target.title.get();
+            let text = if title.is_empty() { label } else { title };
+            rendered.push_str(&body[written..span.start]);
+            rendered.push_str(&format!("[{}](#note-{})", escape_markdown_link_text(&text), target.id));
+            written = span.end;
+        }
+    }
+
+    rendered.push_str(&body[written..]);
+    rendered
+}
+
+/// renders a note body written in commonmark into html that is safe to inject,
+/// bodies are written by other users so anything scripty gets stripped by ammonia
+pub(crate) fn render_markdown(source: &str) -> String {
+    //a line break typed in the editor should stay a line break
+    let parser = pulldown_cmark::Parser::new(source).map(|event| match event {
+        pulldown_cmark::Event::SoftBreak => pulldown_cmark::Event::HardBreak,
+        event => event,
+    });
+
+    let mut unsafe_html = String::with_capacity(source.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut unsafe_html, parser);
+
+    ammonia::clean(&unsafe_html)
+}
+
+fn filter_input(inp: String) -> String {
+    if inp.is_empty() {
+        return inp;
+    }
+
+    inp.trim().to_owned()
+}
+
+fn parse_text_event_value(e: ev::Event) -> String {
+    event_target_value(&e)
+}
+
+
+/// whether two copies of a note agree on everything the note editor lets you change, apart from last_edit
+fn same_edits(a: &SerializedNote, b: &SerializedNote) -> bool {
+    let fields = |note: &SerializedNote| (note.title.clone(), note.body.clone(), note.links.clone(), note.tags.clone(), note.kind, note.items.clone(), note.attachments.clone(), note.due.clone(), note.reminder.clone(), note.recurrence.clone());
+    fields(a) == fields(b)
+}
+
+#[component]
+#[allow(unused_variables)]
+fn NoteEdit(cx: Scope, note: ReadSignal<Note>, viewer: RwSignal<User>, edit_focused_note: RwSignal<Option<Note>>, focused_view_note: RwSignal<Option<Note>>, write_notes: WriteSignal<Vec<Note>>, autosave: Autosave) -> impl IntoView {
+    let body_update_notifier = create_rw_signal(cx, false);
+    let title_update_notifier = create_rw_signal(cx, false);
+
+    let limits = expect_context::<RwSignal<NoteLimits>>(cx);
+    let input_ref = create_node_ref::<Input>(cx);
+
+    //title and body are drafted here and autosaved once typing stops, the snapshot lets cancel put back everything the editor changed
+    let original = SerializedNote::from(&note.get_untracked());
+    let draft_title = create_rw_signal(cx, original.title.clone());
+    let draft_body = create_rw_signal(cx, original.body.clone());
+    let original = store_value(cx, original);
+
+    //anything past the limit is cut off, and the input is put back in step with what was kept
+    let update_title = move |e: ev::Event| {
+        let typed = filter_input(parse_text_event_value(e));
+        let str = limits.get_untracked().truncate_title(typed.clone());
+
+        if str != typed {
+            if let Some(input) = input_ref.get() {
+                input.set_value(&str);
+            }
+        }
+        title_update_notifier.set(true);
+        draft_title.set(str);
+    };
+
+    let notes = expect_context::<ReadSignal<Vec<Note>>>(cx);
+
+    let body_ref = create_node_ref::<Textarea>(cx);
+    let (link_query, set_link_query) = create_signal(cx, None::<(usize, String)>);
+
+    //the textarea's selection is counted in utf-16 units, not bytes
+    let text_before_cursor = move || {
+        let textarea = body_ref.get()?;
+        let cursor = textarea.selection_start().ok().flatten()? as usize;
+        let utf16 = textarea.value().encode_utf16().take(cursor).collect::<Vec<_>>();
+        Some(String::from_utf16_lossy(&utf16))
+    };
+
+    //trimmed on save rather than here, or a trailing newline would vanish as it's typed
+    let update_body = move |e: ev::Event| {
+        body_update_notifier.set(true);
+        let typed = parse_text_event_value(e);
+        let str = limits.get_untracked().truncate_body(typed.clone());
+
+        if str != typed {
+            if let Some(textarea) = body_ref.get() {
+                textarea.set_value(&str);
+            }
+        }
+        draft_body.set(str);
+        set_link_query.set(text_before_cursor().and_then(|before| open_wiki_link(&before)));
+    };
+
+    let link_suggestions = move || {
+        let (_, partial) = link_query.get()?;
+        let partial = partial.to_lowercase();
+        let id = note.get().id;
+
+        let titles = notes.with(|notes| {
+            notes.iter()
+                .filter(|other| other.id != id)
+                .map(|other| other.title.get())
+                .filter(|title| !title.is_empty() && title.to_lowercase().contains(&partial))
+                .take(5)
+                .collect::<Vec<_>>()
+        });
+
+        (!titles.is_empty()).then_some(titles)
+    };
+
+    let complete_link = move |title: String| {
+        let (Some(textarea), Some(before), Some((start, _))) = (body_ref.get(), text_before_cursor(), link_query.get()) else {
+            return;
+        };
+
+        let after = textarea.value()[before.len()..].to_owned();
+        let completed = format!("{}[[{}]]", &before[..start], title);
+        let cursor = completed.encode_utf16().count() as u32;
+
+        textarea.set_value(&format!("{}{}", completed, after));
+        let _ = textarea.set_selection_range(cursor, cursor);
+
+        draft_body.set(textarea.value());
+        set_link_query.set(None);
+    };
+
+    let apply_drafts = move || {
+        let note = note.get_untracked();
+        let (title, body) = (draft_title.get_untracked(), filter_input(draft_body.get_untracked()));
+
+        if title != note.title.get_untracked() {
+            note.update_title(title);
+        }
+        if body != note.body.get_untracked() {
+            note.update_body(body);
+            notes.with_untracked(|notes| note.resolve_links(notes));
+        }
+    };
+
+    //the editor's signals are gone once it closes, and a focused input can still fire blur on its way out
+    let flush = move || {
+        if !note.get_untracked().is_editing.get_untracked() {
+            return;
+        }
+
+        apply_drafts();
+        autosave.save(note.get_untracked());
+    };
+
+    //anything typed, ticked or picked gets saved once the editor has sat idle for a moment
+    create_effect(cx, move |previous: Option<(String, String, SerializedNote)>| {
+        let current = (draft_title.get(), draft_body.get(), SerializedNote::from(&note.get()));
+        if previous.as_ref().is_some_and(|previous| *previous != current) {
+            autosave.schedule(flush);
+        }
+        current
+    });
+
+    let save = move || {
+        apply_drafts();
+        let note = note.get_untracked();
+
+        //tags, checklist items etc. bump last_edit as they go, put it back if they ended up where they started
+        original.with_value(|original| {
+            if same_edits(&SerializedNote::from(&note), original) {
+                note.last_edit.set(original.last_edit.clone());
+            }
+        });
+
+        //closed first so this goes up as the save that ends the edit rather than a draft
+        note.unedit();
+        autosave.save(note);
+    };
+
+    //anything autosaved since the editor opened gets rolled back on the server too
+    let cancel = move || {
+        let note = note.get_untracked();
+        note.restore_edits(original.get_value());
+        note.unedit();
+        autosave.save(note);
+    };
+
+    let title_keydown = move |e: ev::KeyboardEvent| match e.key().as_str() {
+        "Enter" => {
+            e.prevent_default();
+            save();
+        }
+        "Escape" => cancel(),
+        _ => {}
+    };
+
+    //plain enter is left alone so the body can have line breaks
+    let body_keydown = move |e: ev::KeyboardEvent| match e.key().as_str() {
+        "Enter" if e.ctrl_key() || e.meta_key() => {
+            e.prevent_default();
+            save();
+        }
+        "Escape" if link_query.get_untracked().is_some() => set_link_query.set(None),
+        "Escape" => cancel(),
+        _ => {}
+    };
+
+    let (_reached_body_limit, set_reached_body_limit) = create_signal(cx, count_chars(&note.get().body.get()) > limits.get_untracked().body);
+    let (_reached_title_limit, set_reached_title_limit) = create_signal(cx, count_chars(&note.get().title.get()) > limits.get_untracked().title);
+
+
+    create_effect(cx, move |_| {
+        if let Some(input) = input_ref.get() {
+            request_animation_frame(move || {
+                let _ = input.focus();
+            })
+        }
+    });
+
+
+    let body_timeout_handle = create_rw_signal(cx, None);
+    let title_timeout_handle = create_rw_signal(cx, None);
+
+    let notifier_body = view!{
+        cx,
+        <CharacterLimit limit=limits.get_untracked().body text=draft_body reached_limit=set_reached_body_limit/>
+    };
+
+        let notifier_title = view!{
+        cx,
+        <CharacterLimit limit=limits.get_untracked().title text=draft_title reached_limit=set_reached_title_limit/>
+    };
+
+    view!{
+        cx,
+        <div>
+            <h1 class="text_edit_title"> "title" </h1>
+            <input type="text" class="reset bottom_border single_line_text_input" node_ref=input_ref placeholder=move || note.get().title.get() value=draft_title.get_untracked() on:keydown=title_keydown on:input=update_title on:blur=move |_| flush()/>
+            <TimeoutNotifier body=notifier_title show_state=title_update_notifier timeout_duration=2000 timeout_handler=title_timeout_handle/>
+
+            <h1 class="text_edit_body"> "tags" </h1>
+            <input type="text" class="reset bottom_border single_line_text_input" placeholder="comma, separated, tags" value=note.get().tags.get().join(", ") on:change=move |e| note.get().set_tags(parse_tags(&event_target_value(&e)))/>
+
+            <div>
+                <h1 class="reset small_details">
+                    "checklist "
+                    <input type="checkbox" prop:checked=move || note.get().is_checklist() on:change=move |e| {
+                        let kind = if event_target_checked(&e) { NoteKind::Checklist } else { NoteKind::Text };
+                        note.get().set_kind(kind);
+                    }/>
+                </h1>
+            </div>
+
+            {move || if note.get().is_checklist() {
+                view!{cx, <ChecklistEdit note/>}.into_view(cx)
+            }else{
+                view!{
+                    cx,
+                    <div>
+                        <h1 class="text_edit_body"> "body" </h1>
+                        <textarea class="reset bottom_border multi_line_text_input" node_ref=body_ref prop:value=draft_body.get_untracked() placeholder=move || note.get().body.get() on:keydown=body_keydown on:input=update_body on:blur=move |_| flush() />
+
+                        {move || link_suggestions().map(|titles| view!{
+                            cx,
+                            <ul class="reset link_suggestions">
+                                {titles.into_iter().map(|title| {
+                                    let label = title.clone();
+                                    view!{cx, <li on:mousedown=move |e| { e.prevent_default(); complete_link(label.clone()) }>{title}</li>}
+                                }).collect_view(cx)}
+                            </ul>
+                        })}
+
+                        <TimeoutNotifier body=notifier_body.clone() show_state=body_update_notifier timeout_duration=2000 timeout_handler=body_timeout_handle/>
+                    </div>
+                }.into_view(cx)
+            }}
+
+            <div class="flex_seperator">
+                <h1 class="reset small_details">
+                    "due "
+                    <input type="datetime-local" value=note.get().due.get().map(|due| due.to_local_input()) on:change=move |e| note.get().set_due(TimeDate::from_local_input(&event_target_value(&e)))/>
+                </h1>
+                <h1 class="reset small_details">
+                    "remind me "
+                    <input type="datetime-local" value=note.get().reminder.get().map(|reminder| reminder.to_local_input()) on:change=move |e| {
+                        request_notification_permission();
+                        note.get().set_reminder(TimeDate::from_local_input(&event_target_value(&e)));
+                    }/>
+                </h1>
+            </div>
+
+            {move || note.get().due.get().is_some().then(|| view!{cx, <RecurrenceEdit note/>})}
+
+            <AttachmentUpload note/>
+
+            <div class="flex_seperator">
+                <button class="reset share_btn" on:click=move |_| save()> "save" </button>
+                <button class="reset share_btn" on:click=move |_| cancel()> "cancel" </button>
+                <h1 class="reset small_details">"ctrl+enter to save, esc to cancel"</h1>
+            </div>
+        </div>
+    }
+}
+
+
+#[derive(PartialEq, Eq, Clone)]
+pub enum SaveState {
+    Idle,
+    Saving,
+    Saved,
+    Failed(String),
+}
+
+/// debounced saving for a note, keystrokes within the idle window become one request
+/// and a save that starts while another is running waits so they land in order.
+///
+/// autosave wins over holding edits back until the editor is saved: whatever is typed
+/// reaches the server within a moment so a closed tab or failed save can't lose it.
+/// saves made while the note is being edited go up as drafts, which webhooks don't
+/// hear about until save or cancel closes the editor
+#[derive(Clone, Copy)]
+pub struct Autosave {
+    cx: Scope,
+    state: RwSignal<SaveState>,
+    timer: RwSignal<Option<TimeoutHandle>>,
+    in_flight: RwSignal<bool>,
+    queued: RwSignal<bool>,
+    /// the last note sent and whether it was a draft
+    last_sent: StoredValue<Option<(SerializedNote, bool)>>,
+}
+
+impl Autosave {
+    const IDLE_MILLIS: u64 = 1500;
+
+    fn new(cx: Scope) -> Self {
+        Self {
+            cx,
+            state: create_rw_signal(cx, SaveState::Idle),
+            timer: create_rw_signal(cx, None),
+            in_flight: create_rw_signal(cx, false),
+            queued: create_rw_signal(cx, false),
+            last_sent: store_value(cx, None),
+        }
+    }
+
+    fn cancel_pending(&self) {
+        if let Some(timer) = self.timer.get_untracked() {
+            timer.clear();
+        }
+        self.timer.set(None);
+    }
+
+    /// (re)starts the idle timer, `flush` runs once nothing has called this for a while
+    fn schedule(&self, flush: impl FnOnce() + 'static) {
+        self.cancel_pending();
+
+        let timer = self.timer;
+        let handle = set_timeout_with_handle(move || {
+            timer.set(None);
+            flush();
+        }, core::time::Duration::from_millis(Self::IDLE_MILLIS)).ok();
+
+        self.timer.set(handle);
+    }
+
+    /// sends the note as it is now, the local copy is never touched so a failed save loses nothing
+    fn save(&self, note: Note) {
+        let draft = note.is_editing.get_untracked();
+        self.cancel_pending();
+
+        if self.in_flight.get_untracked() {
+            self.queued.set(true);
+            return;
+        }
+
+        //failures clear last_sent, so this only skips notes the server already has.
+        //the save that ends an edit still goes out so webhooks hear about the drafts
+        let snapshot = SerializedNote::from(&note);
+        if self.last_sent.with_value(|sent| sent.as_ref().is_some_and(|(sent, sent_draft)| *sent == snapshot && (draft || !sent_draft))) {
+            return;
+        }
+
+        self.in_flight.set(true);
+        self.state.set(SaveState::Saving);
+        self.last_sent.set_value(Some((snapshot.clone(), draft)));
+
+        let autosave = *self;
+        spawn_local(async move {
+            let result = save_note(autosave.cx, snapshot, draft).await;
+            autosave.in_flight.set(false);
+
+            let failure = match result {
+                Ok(Ok(())) => None,
+                Ok(Err(invalid)) => Some(invalid.to_string()),
+                Err(e) => Some(e.to_string()),
+            };
+
+            match failure {
+                None => autosave.state.set(SaveState::Saved),
+                Some(e) => {
+                    log!("failed to save note: {}", e);
+                    autosave.last_sent.set_value(None);
+                    autosave.state.set(SaveState::Failed(e));
+                }
+            }
+
+            if autosave.queued.get_untracked() {
+                autosave.queued.set(false);
+                autosave.save(note);
+            }
+        });
+    }
+}
+
+#[component]
+fn SaveStatus(cx: Scope, autosave: Autosave, note: ReadSignal<Note>) -> impl IntoView {
+    view!{
+        cx,
+        {move || match autosave.state.get() {
+            SaveState::Idle => None,
+            SaveState::Saving => Some(view!{cx, <h1 class="reset small_details save_status">"Saving…"</h1>}.into_view(cx)),
+            SaveState::Saved => Some(view!{cx, <h1 class="reset small_details save_status">"Saved"</h1>}.into_view(cx)),
+            SaveState::Failed(error) => Some(view!{
+                cx,
+                <h1 class="reset small_details save_status save_failed" title=error>
+                    "Failed — "
+                    <button class="reset share_btn" on:click=move |_| autosave.save(note.get_untracked())> "retry" </button>
+                </h1>
+            }.into_view(cx)),
+        }}
+    }
+}
+
+#[component]
+fn TimeoutNotifier(cx: Scope, body: View, show_state: RwSignal<bool>, timeout_duration: u64, timeout_handler: RwSignal<Option<TimeoutHandle>>) -> impl IntoView {
+    //only show_state is tracked, writing it back from in here would retrigger the effect forever
+    create_effect(cx, move |_| {
+        if show_state.get() {
+            if let Some(timeout) = timeout_handler.get_untracked() {
+                timeout.clear();
+            }
+
+            let new_timeout = set_timeout_with_handle(move || {
+                show_state.set(false);
+                timeout_handler.set(None)
+            }, core::time::Duration::from_millis(timeout_duration)).ok();
+
+            timeout_handler.set(new_timeout);
+        }
+    });
+
+    //stays mounted so it can fade out instead of vanishing mid keystroke
+    view!{
+        cx, 
+        <div class="timeout_notifier" aria-hidden=move || (!show_state.get()).to_string() style:opacity=move || if show_state.get() { "1" } else { "0" } style:pointer-events=move || if show_state.get() { "auto" } else { "none" } style:transition="opacity 0.4s ease-out">
+            {body}
+        </div>
+    }
+}
+
+pub enum TextColorState {
+    Normal,
+    Warning,
+    Limit,
+    OverLimit
+}
+
+//notes are pastel, so these need to stay readable on a light background
+impl std::fmt::Display for TextColorState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let color = match *self {
+            Self::Normal => "inherit",
+            Self::Warning => "darkorange",
+            Self::Limit => "red",
+            Self::OverLimit => "darkred",
+        };
+        f.write_str(color)
+    }
+}
+
+//most severe first, otherwise the warning branch swallows everything past half way
+fn set_color_from_state(text_length: u64, text_limit: u64) -> TextColorState {
+    if text_length > text_limit {
+        TextColorState::OverLimit
+    }else if text_length == text_limit {
+        TextColorState::Limit
+    }else if text_length > text_limit / 2 {
+        TextColorState::Warning
+    }else{
+        TextColorState::Normal
+    }
+}
+
+#[component] 
+fn CharacterLimit(cx: Scope, limit: u64, text: RwSignal<String>, reached_limit: WriteSignal<bool>) -> impl IntoView {
+    let length = create_memo(cx, move |_| text.with(|text| count_chars(text)));
+    let words = create_memo(cx, move |_| text.with(|text| count_words(text)));
+
+    let (color_state, set_color_state) = create_signal(cx, set_color_from_state(length.get_untracked(), limit).to_string());
+
+    create_effect(cx, move |_| {
+        set_color_state.set(set_color_from_state(length.get(), limit).to_string());
+
+        if length.get() > limit {
+            reached_limit.set(true)
+        }else {
+            reached_limit.set(false)
+        }
+    });
+
+    view!{
+        cx,
+        <div class="character_limit" style:color=move || color_state.get()>
+            <h1 class="reset small_details">{move || length.get()} "/" {limit} {move || (length.get() > limit).then_some(" over the limit")}</h1>
+            <h1 class="reset small_details">{move || words.get()} {move || if words.get() == 1 { " word" } else { " words" }}</h1>
+        </div>
+    }
+}
+
+#[component]
+fn EditButton(cx: Scope, edit_state: RwSignal<bool>) -> impl IntoView  {
+    let toggle_edit = move || edit_state.update(|state| *state = !*state);
+
+    let img_url = Signal::derive(cx, move || if edit_state.get() {
+        "https://cdn-icons-png.flaticon.com/512/3395/3395544.png"
+    }else{
+        "https://cdn-icons-png.flaticon.com/512/860/860814.png"
+    });
+
+    view!{
+        cx,
+        <div class="edit_btn">
+            <ImgBtn link=img_url.get() on_click=toggle_edit/>
+        </div>
+    }
+}
+
+#[component]
+fn Login(cx: Scope) -> impl IntoView {
+    view!{
+        cx,
+        <div>
+            <h1>"Login"</h1>
+
+
+        </div>
+    }
+}
+
+#[derive(PartialEq, Eq, Clone)]
+enum NotesMode {
+    Pinned,
+    Queried(String),
+    PinnedQueried(String),
+    Normal,
+}
+
+impl SerializedNote {
+    fn into_normal(self, cx: Scope) -> Note {
+
+        let SerializedNote {
+            id,
+            title,
+            body,
+            user,
+            date,
+            last_edit,
+            access,
+            share_link,
+            comments,
+            kind,
+            items,
+            attachments,
+            due,
+            reminder,
+            reminder_sent,
+            recurrence,
+            links,
+            tags,
+            colour,
+            pinned,
+            in_feed,
+        } = self;
+
+        let title = create_rw_signal(cx, title);
+        let body = create_rw_signal(cx, body);
+        let last_edit = create_rw_signal(cx, last_edit);
+        let access = create_rw_signal(cx, access);
+        let share_link = create_rw_signal(cx, share_link);
+        let comments = create_rw_signal(cx, comments);
+        let kind = create_rw_signal(cx, kind);
+        let items = create_rw_signal(cx, items);
+        let attachments = create_rw_signal(cx, attachments);
+        let due = create_rw_signal(cx, due);
+        let reminder = create_rw_signal(cx, reminder);
+        let reminder_sent = create_rw_signal(cx, reminder_sent);
+        let recurrence = create_rw_signal(cx, recurrence);
+        let links = create_rw_signal(cx, links);
+        let tags = create_rw_signal(cx, tags);
+        let in_feed = create_rw_signal(cx, in_feed);
+
+        let pinned = create_rw_signal(cx, pinned);
+        let maximized = create_rw_signal(cx, false);
+        let minimized = create_rw_signal(cx, false);
+        let is_editing = create_rw_signal(cx, false);
+
+        let user = user.into_normal();
+
+        Note {
+            id,
+            title,
+            body,
+            user,
+            date,
+            last_edit,
+            pinned,
+            maximized,
+            minimized,
+            is_editing,
+            access,
+            share_link,
+            comments,
+            kind,
+            items,
+            attachments,
+            due,
+            reminder,
+            reminder_sent,
+            recurrence,
+            links,
+            tags,
+            in_feed,
+            colour,
         }
     }
 }
 
-fn set_color_from_state(text_length: u64, text_limit: u64) -> TextColorState {
-    if text_length > text_limit / 2 {
-        TextColorState::Warning
-    }else if text_length == text_limit {
-        TextColorState::Limit
-    }else if text_length >= text_limit {
-        TextColorState::OverLimit
-    }else{
-        TextColorState::Normal
+impl From<&Note> for SerializedNote {
+    fn from(from: &Note) -> SerializedNote {
+        let Note {
+            id,
+            title,
+            body,
+            user,
+            date,
+            last_edit,
+            access,
+            share_link,
+            comments,
+            kind,
+            items,
+            attachments,
+            due,
+            reminder,
+            reminder_sent,
+            recurrence,
+            links,
+            tags,
+            colour,
+            pinned,
+            in_feed,
+            ..
+        } = from;
+
+        SerializedNote {
+            id: *id,
+            title: title.get(),
+            body: body.get(),
+            user: user.into(),
+            date: date.clone(),
+            last_edit: last_edit.get(),
+            access: access.get(),
+            share_link: share_link.get(),
+            comments: comments.get(),
+            kind: kind.get(),
+            items: items.get(),
+            attachments: attachments.get(),
+            due: due.get(),
+            reminder: reminder.get(),
+            reminder_sent: reminder_sent.get(),
+            recurrence: recurrence.get(),
+            links: links.get(),
+            tags: tags.get(),
+            colour: colour.clone(),
+            pinned: pinned.get(),
+            in_feed: in_feed.get(),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn forbidden() -> ServerFnError {
+    ServerFnError::ServerError("you don't have access to that note".to_owned())
+}
+
+/// saves the caller's copy of a note over the stored one, new notes belong to the caller.
+/// a note over the character limits comes back as the inner error. `draft` saves come
+/// from an editor that's still open, webhooks wait until the edit is saved or cancelled
+#[server(SaveNote, "/api")]
+pub async fn save_note(cx: Scope, note: SerializedNote, draft: bool) -> Result<Result<(), NoteValidationError>, ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?;
+    let limits = crate::config::get().note_limits();
+
+    let saved = crate::store::write(|store| -> Result<_, ServerFnError> {
+        let stored = store.notes.get(&note.id);
+
+        let (previous_title, previous_body) = stored.map_or(("", ""), |stored| (stored.title.as_str(), stored.body.as_str()));
+        if let Err(invalid) = limits.check_edit(&note.title, &note.body, previous_title, previous_body) {
+            return Ok(Err(invalid));
+        }
+
+        let note = match stored {
+            Some(stored) => stored.apply_edit(note, caller.uuid).ok_or_else(forbidden)?,
+            None => SerializedNote { user: caller, access: Vec::new(), share_link: None, comments: Vec::new(), in_feed: false, ..note },
+        };
+
+        let previous = store.notes.insert(note.id, note.clone());
+        let events = crate::webhooks::events_for_save(&mut store.drafts, previous.clone(), &note, draft);
+
+        //attachments removed in this edit may have been the last reference to their blob
+        if let Some(previous) = previous {
+            let referenced = store.referenced_blobs();
+            crate::attachments::remove_unreferenced(previous.attachments.iter().map(|a| a.blob.as_str()), &referenced);
+        }
+
+        Ok(Ok((note, events)))
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))??;
+
+    let (note, events) = match saved {
+        Ok(saved) => saved,
+        Err(invalid) => return Ok(Err(invalid)),
+    };
+
+    crate::webhooks::dispatch(&note, &events);
+    Ok(Ok(()))
+}
+
+/// adds a comment from the caller to a note they can see
+#[server(AddComment, "/api")]
+pub async fn add_comment(cx: Scope, note_id: Uuid, body: String) -> Result<Comment, ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?;
+    let comment = Comment::new(caller.clone(), body);
+
+    let note = crate::store::write(|store| -> Result<_, ServerFnError> {
+        let note = store.notes.get_mut(&note_id)
+            .filter(|note| note.access_level(caller.uuid).is_some())
+            .ok_or_else(forbidden)?;
+        note.comments.push(comment.clone());
+        Ok(note.clone())
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))??;
+
+    crate::webhooks::dispatch(&note, &[NoteEvent::Commented]);
+    Ok(comment)
+}
+
+/// rewrites one of the caller's own comments
+#[server(EditComment, "/api")]
+pub async fn edit_comment(cx: Scope, note_id: Uuid, id: Uuid, body: String) -> Result<Comment, ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+
+    crate::store::write(|store| -> Result<_, ServerFnError> {
+        let comment = store.notes.get_mut(&note_id)
+            .and_then(|note| note.comments.iter_mut().find(|c| c.id == id && c.author.uuid == caller))
+            .ok_or_else(forbidden)?;
+        comment.body = body;
+        comment.last_edit = Some(TimeDate::default());
+        Ok(comment.clone())
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+}
+
+/// removes one of the caller's own comments
+#[server(DeleteComment, "/api")]
+pub async fn delete_comment(cx: Scope, note_id: Uuid, id: Uuid) -> Result<(), ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+
+    crate::store::write(|store| -> Result<_, ServerFnError> {
+        let comments = &mut store.notes.get_mut(&note_id).ok_or_else(forbidden)?.comments;
+        let position = comments.iter().position(|c| c.id == id && c.author.uuid == caller).ok_or_else(forbidden)?;
+        comments.remove(position);
+        Ok(())
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+}
+
+/// permanently removes a note along with any attachments nothing else points at, owner only
+#[server(DeleteNote, "/api")]
+pub async fn delete_note(cx: Scope, id: Uuid) -> Result<(), ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+
+    let removed = crate::store::write(|store| {
+        match store.notes.get(&id) {
+            Some(note) if note.user.uuid != caller => return Err(forbidden()),
+            Some(_) => {}
+            None => return Ok(None),
+        }
+
+        store.share_links.retain(|_, link| link.note_id != id);
+
+        let removed = store.notes.remove(&id);
+        if let Some(removed) = &removed {
+            let referenced = store.referenced_blobs();
+            crate::attachments::remove_unreferenced(removed.attachments.iter().map(|a| a.blob.as_str()), &referenced);
+        }
+
+        Ok(removed)
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))??;
+
+    if let Some(removed) = removed {
+        crate::webhooks::dispatch(&removed, &[NoteEvent::Deleted]);
+    }
+    Ok(())
+}
+
+/// gives `username` access to one of the caller's notes, or changes the access they have,
+/// returning the note's access list as it is now
+#[server(ShareNote, "/api")]
+pub async fn share_note(cx: Scope, note_id: Uuid, username: String, level: AccessLevel) -> Result<Vec<NoteAccess>, ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+
+    crate::store::write(|store| {
+        let user = store.user_by_name(username.trim()).cloned()
+            .ok_or_else(|| ServerFnError::ServerError(format!("no user called \"{}\"", username.trim())))?;
+        let note = store.notes.get_mut(&note_id).filter(|note| note.user.uuid == caller).ok_or_else(forbidden)?;
+
+        note.share_with(user, level);
+        Ok(note.access.clone())
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+}
+
+/// takes `user` off one of the caller's notes, returning the note's access list as it is now
+#[server(UnshareNote, "/api")]
+pub async fn unshare_note(cx: Scope, note_id: Uuid, user: Uuid) -> Result<Vec<NoteAccess>, ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+
+    crate::store::write(|store| {
+        let note = store.notes.get_mut(&note_id).filter(|note| note.user.uuid == caller).ok_or_else(forbidden)?;
+
+        note.unshare(user);
+        Ok(note.access.clone())
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+}
+
+/// how many names the share dialog suggests at once
+#[cfg(feature = "ssr")]
+const USER_SUGGESTIONS: usize = 8;
+
+/// other users whose names start with `prefix`, for the share dialog's autocomplete
+#[server(FindUsers, "/api")]
+pub async fn find_users(cx: Scope, prefix: String) -> Result<Vec<SerializedUser>, ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+    let prefix = prefix.trim().to_lowercase();
+    if prefix.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut users = crate::store::read(|store| {
+        store.users.values()
+            .filter(|user| user.uuid != caller && user.username.to_lowercase().starts_with(&prefix))
+            .cloned()
+            .collect::<Vec<_>>()
+    });
+
+    users.sort_by(|a, b| a.username.cmp(&b.username));
+    users.truncate(USER_SUGGESTIONS);
+    Ok(users)
+}
+
+/// replaces any existing link for the note, so there is only ever one live token per note.
+/// the note has to be saved already and the caller needs write access to it
+#[server(CreateShareLink, "/api")]
+pub async fn create_share_link(cx: Scope, note_id: Uuid, expires: Option<TimeDate>) -> Result<ShareLink, ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+    let link = ShareLink::new(note_id, expires);
+
+    crate::store::write(|store| {
+        let note = store.notes.get_mut(&note_id).ok_or_else(|| ServerFnError::ServerError("no such note".to_owned()))?;
+        if note.access_level(caller) != Some(AccessLevel::Write) {
+            return Err(forbidden());
+        }
+        note.share_link = Some(link.clone());
+
+        store.share_links.retain(|_, existing| existing.note_id != note_id);
+        store.share_links.insert(link.token.clone(), link.clone());
+        Ok(())
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))??;
+
+    Ok(link)
+}
+
+/// lists one of the caller's notes in their public feed, or takes it out. only notes
+/// with a live share link show up there
+#[server(SetInFeed, "/api")]
+pub async fn set_in_feed(cx: Scope, note_id: Uuid, in_feed: bool) -> Result<(), ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+
+    crate::store::write(|store| {
+        let note = store.notes.get_mut(&note_id).filter(|note| note.user.uuid == caller).ok_or_else(forbidden)?;
+        note.in_feed = in_feed;
+        Ok(())
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+}
+
+/// only marks the reminder as sent, so it can't save over edits still open in the editor.
+/// a reminder that changed since it went off is left armed
+#[server(MarkReminderSent, "/api")]
+pub async fn mark_reminder_sent(cx: Scope, note_id: Uuid, reminder: TimeDate) -> Result<(), ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+
+    crate::store::write(|store| {
+        let note = store.notes.get_mut(&note_id).filter(|note| note.access_level(caller) == Some(AccessLevel::Write)).ok_or_else(forbidden)?;
+        if note.reminder.as_ref() == Some(&reminder) {
+            note.reminder_sent = true;
+        }
+        Ok(())
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+}
+
+#[server(RevokeShareLink, "/api")]
+pub async fn revoke_share_link(cx: Scope, token: String) -> Result<(), ServerFnError> {
+    let caller = crate::sessions::current_user(cx)?.uuid;
+
+    crate::store::write(|store| {
+        let Some(link) = store.share_links.get(&token) else {
+            return Ok(());
+        };
+        let note = store.notes.get_mut(&link.note_id);
+        if note.as_ref().is_some_and(|note| note.access_level(caller) != Some(AccessLevel::Write)) {
+            return Err(forbidden());
+        }
+
+        if let Some(note) = note {
+            note.share_link = None;
+        }
+        store.share_links.remove(&token);
+        Ok(())
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+}
+
+#[server(GetSharedNote, "/api")]
+pub async fn get_shared_note(token: String) -> Result<Option<SerializedNote>, ServerFnError> {
+    Ok(crate::store::read(|store| store.shared_note(&token).map(|note| note.clone().visible_to(Uuid::nil()))))
+}
+
+#[server(GetNoteLimits, "/api")]
+pub async fn get_note_limits() -> Result<NoteLimits, ServerFnError> {
+    Ok(crate::config::get().note_limits())
+}
+
+/// whoever the session cookie belongs to, if anyone
+#[server(SignedInUser, "/api")]
+pub async fn signed_in_user(cx: Scope) -> Result<Option<SerializedUser>, ServerFnError> {
+    Ok(crate::sessions::current_user(cx).ok())
+}
+
+/// signs in with a username and password, the same error comes back whichever was wrong
+#[server(SignIn, "/api")]
+pub async fn sign_in(cx: Scope, username: String, password: String) -> Result<SerializedUser, ServerFnError> {
+    let user = crate::sessions::check_password(username.trim(), &password)
+        .ok_or_else(|| ServerFnError::ServerError("wrong username or password".to_owned()))?;
+
+    crate::sessions::start(cx, user.uuid)?;
+    Ok(user)
+}
+
+/// makes an account and signs into it
+#[server(SignUp, "/api")]
+pub async fn sign_up(cx: Scope, username: String, password: String) -> Result<SerializedUser, ServerFnError> {
+    let username = username.trim();
+    if username.is_empty() {
+        return Err(ServerFnError::ServerError("usernames can't be empty".to_owned()));
+    }
+    if password.chars().count() < crate::sessions::MIN_PASSWORD_LENGTH {
+        return Err(ServerFnError::ServerError(format!("passwords need at least {} characters", crate::sessions::MIN_PASSWORD_LENGTH)));
     }
-}
 
-#[component] 
-fn CharacterLimit(cx: Scope, limit: u64, text: RwSignal<String>, reached_limit: WriteSignal<bool>) -> impl IntoView {
-    let (color_state, set_color_state) = create_signal(cx, set_color_from_state(text.get().len() as u64, limit).to_string());
+    let hash = crate::sessions::hash_password(&password)?;
+    let user = crate::store::write(|store| store.register(username, hash))
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
 
-    create_effect(cx, move |_| {
-        set_color_state.set(set_color_from_state(text.get().len() as u64, limit).to_string());
+    crate::sessions::start(cx, user.uuid)?;
+    Ok(user)
+}
 
-        if text.get().len() as u64 > limit {
-            reached_limit.set(true)
-        }else {
-            reached_limit.set(false)
-        }
+/// every note the caller can see, oldest first
+#[server(ListNotes, "/api")]
+pub async fn list_notes(cx: Scope) -> Result<Vec<SerializedNote>, ServerFnError> {
+    let user = crate::sessions::current_user(cx)?.uuid;
+    let mut notes = crate::store::read(|store| {
+        store.notes.values()
+            .filter(|note| note.access_level(user).is_some())
+            .map(|note| note.clone().visible_to(user))
+            .collect::<Vec<_>>()
     });
 
-    view!{
-        cx,
-        <div>
-            <h1>{text.get().len()} "/" {limit} </h1>
-        </div>
-    }
+    notes.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(notes)
 }
 
-#[component]
-fn EditButton(cx: Scope, edit_state: RwSignal<bool>) -> impl IntoView  {
-    let toggle_edit = move || edit_state.update(|state| *state = !*state);
-
-    let img_url = Signal::derive(cx, move || if edit_state.get() {
-        "https://cdn-icons-png.flaticon.com/512/3395/3395544.png"
-    }else{
-        "https://cdn-icons-png.flaticon.com/512/860/860814.png"
+#[server(ListAccessTokens, "/api")]
+pub async fn list_access_tokens(cx: Scope) -> Result<Vec<AccessToken>, ServerFnError> {
+    let user = crate::sessions::current_user(cx)?.uuid;
+    let mut tokens = crate::store::read(|store| {
+        store.access_tokens.values().filter(|token| token.user == user).cloned().collect::<Vec<_>>()
     });
 
-    view!{
-        cx,
-        <div class="edit_btn">
-            <ImgBtn link=img_url.get() on_click=move || toggle_edit()/>
-        </div>
-    }
+    tokens.sort_by(|a, b| a.created.cmp(&b.created));
+    Ok(tokens)
 }
 
-#[component]
-fn Login(cx: Scope) -> impl IntoView {
-    view!{
-        cx,
-        <div>
-            <h1>"Login"</h1>
+/// returns the token metadata along with the secret, which is never retrievable again
+#[server(CreateAccessToken, "/api")]
+pub async fn create_access_token(cx: Scope, name: String) -> Result<(AccessToken, String), ServerFnError> {
+    let user = crate::sessions::current_user(cx)?;
+    let secret = format!("fyn_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+    let token = AccessToken {
+        id: Uuid::new_v4(),
+        user: user.uuid,
+        name,
+        created: TimeDate::default(),
+        last_used: None,
+    };
 
+    crate::store::write(|store| {
+        store.access_tokens.insert(crate::api::hash_token(&secret), token.clone());
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
 
-        </div>
-    }
+    Ok((token, secret))
 }
 
-#[derive(PartialEq, Eq, Clone)]
-enum NotesMode {
-    Pinned,
-    Queried(String),
-    PinnedQueried(String),
-    Normal,
+#[server(RevokeAccessToken, "/api")]
+pub async fn revoke_access_token(cx: Scope, id: Uuid) -> Result<(), ServerFnError> {
+    let user = crate::sessions::current_user(cx)?.uuid;
+    crate::store::write(|store| {
+        store.access_tokens.retain(|_, token| !(token.id == id && token.user == user));
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))
 }
 
-#[derive(Serialize, Deserialize)]
-struct SerializedNote {
-    pub id: Uuid,
-    pub title: String,
-    pub body: String,
-    pub user: SerializedUser,
-    pub date: TimeDate,
-    pub last_edit: Option<TimeDate>,
+/// makes a new calendar subscription link, revoking any older one. times in the
+/// feed are given in `timezone`, the iana name of the subscriber's time zone
+#[server(ResetCalendarLink, "/api")]
+pub async fn reset_calendar_link(cx: Scope, timezone: String) -> Result<String, ServerFnError> {
+    let user = crate::sessions::current_user(cx)?.uuid;
+    let timezone = timezone.parse::<chrono_tz::Tz>()
+        .map_err(|_| ServerFnError::Args(format!("unknown time zone {}", timezone)))?;
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+    crate::store::write(|store| {
+        store.calendar_tokens.retain(|_, owner| *owner != user);
+        store.calendar_tokens.insert(crate::api::hash_token(&token), user);
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    Ok(crate::calendar::path(&token, timezone))
 }
 
-impl SerializedNote {
-    fn into_normal(self, cx: Scope) -> Note {
+#[server(RevokeCalendarLink, "/api")]
+pub async fn revoke_calendar_link(cx: Scope) -> Result<(), ServerFnError> {
+    let user = crate::sessions::current_user(cx)?.uuid;
+    crate::store::write(|store| {
+        store.calendar_tokens.retain(|_, owner| *owner != user);
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))
+}
 
-        let SerializedNote {
-            id,
-            title,
-            body,
-            user,
-            date,
-            last_edit,
-        } = self;
+#[server(ListWebhooks, "/api")]
+pub async fn list_webhooks(cx: Scope) -> Result<Vec<Webhook>, ServerFnError> {
+    let user = crate::sessions::current_user(cx)?.uuid;
+    let mut hooks = crate::store::read(|store| {
+        store.webhooks.values().filter(|hook| hook.user == user).cloned().collect::<Vec<_>>()
+    });
 
-        let title = create_rw_signal(cx, title);
-        let body = create_rw_signal(cx, body);
-        let last_edit = create_rw_signal(cx, last_edit);
+    hooks.sort_by(|a, b| a.created.cmp(&b.created));
+    Ok(hooks)
+}
 
-        let pinned = create_rw_signal(cx, false);
-        let maximized = create_rw_signal(cx, false);
-        let minimized = create_rw_signal(cx, false);
-        let is_editing = create_rw_signal(cx, false);
+#[server(CreateWebhook, "/api")]
+pub async fn create_webhook(cx: Scope, url: String, events: Vec<NoteEvent>) -> Result<Webhook, ServerFnError> {
+    let user = crate::sessions::current_user(cx)?.uuid;
+    crate::webhooks::resolve_target(&url).await.map_err(ServerFnError::Args)?;
+
+    let hook = Webhook {
+        id: Uuid::new_v4(),
+        user,
+        url,
+        secret: format!("whsec_{}", Uuid::new_v4().simple()),
+        events,
+        created: TimeDate::default(),
+        deliveries: Vec::new(),
+    };
 
-        let colour = get_new_pastel_colour();
+    crate::store::write(|store| {
+        store.webhooks.insert(hook.id, hook.clone());
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
 
-        let user = user.into_normal();
+    Ok(hook)
+}
 
-        Note {
-            id,
-            title,
-            body,
-            user,
-            date,
-            last_edit,
-            pinned,
-            maximized,
-            minimized,
-            is_editing,
-            colour,
-        }
-    }
+#[server(DeleteWebhook, "/api")]
+pub async fn delete_webhook(cx: Scope, id: Uuid) -> Result<(), ServerFnError> {
+    let user = crate::sessions::current_user(cx)?.uuid;
+    crate::store::write(|store| {
+        store.webhooks.retain(|_, hook| !(hook.id == id && hook.user == user));
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))
 }
 
-impl From<&Note> for SerializedNote {
-    fn from(from: &Note) -> SerializedNote {
-        let Note {
-            id,
-            title,
-            body,
-            user,
-            date,
-            last_edit,
-            ..
-        } = from;
+#[server(ListTemplates, "/api")]
+pub async fn list_templates(cx: Scope) -> Result<Vec<NoteTemplate>, ServerFnError> {
+    let owner = crate::sessions::current_user(cx)?.uuid;
+    let mut templates = crate::store::read(|store| {
+        store.templates.values().filter(|template| template.owner == owner).cloned().collect::<Vec<_>>()
+    });
 
-        SerializedNote {
-            id: id.clone(),
-            title: title.get(),
-            body: body.get(),
-            user: user.into(),
-            date: date.clone(),
-            last_edit: last_edit.get(),
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+/// saves a template for the caller, whatever owner it was sent with. a template over the
+/// character limits comes back as the inner error, like `save_note`
+#[server(SaveTemplate, "/api")]
+pub async fn save_template(cx: Scope, template: NoteTemplate) -> Result<Result<(), NoteValidationError>, ServerFnError> {
+    let owner = crate::sessions::current_user(cx)?.uuid;
+    let limits = crate::config::get().note_limits();
+    let template = NoteTemplate { owner, ..template };
+
+    crate::store::write(|store| -> Result<_, ServerFnError> {
+        let stored = store.templates.get(&template.id);
+        if stored.is_some_and(|stored| stored.owner != owner) {
+            return Err(forbidden());
         }
-    }
+
+        let (previous_title, previous_body) = stored.map_or(("", ""), |stored| (stored.title.as_str(), stored.body.as_str()));
+        if let Err(invalid) = limits.check_edit(&template.title, &template.body, previous_title, previous_body) {
+            return Ok(Err(invalid));
+        }
+
+        store.templates.insert(template.id, template);
+        Ok(Ok(()))
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?
 }
 
 //TODO: serde needs to be derived and figured out for this and then can store in localstorage
 
 
-/**
+/*
 fn get_stored_notes(cx: Scope) -> Vec<Note> {
     let starting_notes = if let Some(storage) = window().local_storage() {
         storage.get_item(STORAGE_KEY)
@@ -868,7 +3186,319 @@ fn get_stored_notes(cx: Scope) -> Vec<Note> {
             Vec::new()
         };
 }
-**/
+*/
+
+impl NotesExport {
+    fn new(user: &User, notes: &[Note]) -> Self {
+        Self {
+            version: EXPORT_VERSION,
+            exported: TimeDate::default(),
+            user: user.into(),
+            notes: notes.iter().filter(|note| note.user.uuid == user.uuid).map(SerializedNote::from).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct ImportReport {
+    dry_run: bool,
+    created: Vec<String>,
+    updated: Vec<String>,
+    skipped: Vec<(String, &'static str)>,
+}
+
+impl ImportReport {
+    fn record(&mut self, title: String, action: ImportAction) {
+        match action {
+            ImportAction::Create => self.created.push(title),
+            ImportAction::Update => self.updated.push(title),
+            ImportAction::Skip(reason) => self.skipped.push((title, reason)),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ImportAction {
+    Create,
+    Update,
+    Skip(&'static str),
+}
+
+fn display_title(title: &str) -> String {
+    if title.is_empty() { "untitled note".to_owned() } else { title.to_owned() }
+}
+
+/// what importing a note would do, matching on id
+fn plan_import(imported: &SerializedNote, notes: &[Note], user: &User, limits: &NoteLimits) -> ImportAction {
+    let existing = notes.iter().find(|note| note.id == imported.id);
+
+    //the server would refuse these, so they're skipped here where the report can say why
+    let too_long = || {
+        let (previous_title, previous_body) = existing.map_or((String::new(), String::new()), |note| (note.title.get(), note.body.get()));
+        limits.check_edit(&imported.title, &imported.body, &previous_title, &previous_body).is_err()
+    };
+
+    match existing {
+        Some(existing) if existing.user.uuid != user.uuid => ImportAction::Skip("belongs to someone else"),
+        Some(existing) if imported.last_edit.is_none() || imported.last_edit <= existing.last_edit.get() => {
+            ImportAction::Skip("already up to date")
+        }
+        _ if too_long() => ImportAction::Skip("longer than the character limit"),
+        Some(_) => ImportAction::Update,
+        None => ImportAction::Create,
+    }
+}
+
+fn preview_import(imported_notes: &[SerializedNote], notes: &[Note], user: &User, limits: &NoteLimits) -> ImportReport {
+    let mut report = ImportReport { dry_run: true, ..ImportReport::default() };
+
+    for imported in imported_notes {
+        report.record(display_title(&imported.title), plan_import(imported, notes, user, limits));
+    }
+
+    report
+}
+
+/// merges imported notes into the current notes. imported notes are re-owned by
+/// the importing user so moving between instances keeps them editable, and start
+/// out unshared since the people and links they were shared with don't carry over.
+/// `overwrite` updates notes that already exist
+fn import_notes(
+    cx: Scope,
+    imported_notes: Vec<SerializedNote>,
+    notes: &mut Vec<Note>,
+    user: &User,
+    limits: &NoteLimits,
+    overwrite: fn(&Note, SerializedNote),
+) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    for imported in imported_notes {
+        let title = display_title(&imported.title);
+        let action = plan_import(&imported, notes, user, limits);
+
+        match action {
+            ImportAction::Update => {
+                if let Some(existing) = notes.iter().find(|note| note.id == imported.id) {
+                    overwrite(existing, imported);
+                    existing.mirror_to_db(cx);
+                }
+            }
+            ImportAction::Create => {
+                let note = SerializedNote {
+                    user: user.into(),
+                    access: Vec::new(),
+                    share_link: None,
+                    comments: Vec::new(),
+                    in_feed: false,
+                    ..imported
+                }
+                .into_normal(cx);
+                note.mirror_to_db(cx);
+                notes.push(note);
+            }
+            ImportAction::Skip(_) => {}
+        }
+
+        report.record(title, action);
+    }
+
+    report
+}
+
+fn download_file(filename: &str, contents: &[u8], mime: &str) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(contents));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let anchor = document().create_element("a")?.unchecked_into::<web_sys::HtmlAnchorElement>();
+    anchor.set_href(&url);
+    anchor.set_download(filename);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url)
+}
+
+async fn read_file_bytes(file: web_sys::File) -> Result<Vec<u8>, String> {
+    wasm_bindgen_futures::JsFuture::from(file.array_buffer())
+        .await
+        .map(|buffer| js_sys::Uint8Array::new(&buffer).to_vec())
+        .map_err(|_| format!("couldn't read {}", file.name()))
+}
+
+async fn read_file_text(file: web_sys::File) -> Result<String, String> {
+    wasm_bindgen_futures::JsFuture::from(file.text())
+        .await
+        .ok()
+        .and_then(|text| text.as_string())
+        .ok_or_else(|| format!("couldn't read {}", file.name()))
+}
+
+#[component]
+fn BackupControls(cx: Scope, user: RwSignal<User>) -> impl IntoView {
+    let notes = expect_context::<ReadSignal<Vec<Note>>>(cx);
+    let set_notes = expect_context::<WriteSignal<Vec<Note>>>(cx);
+    let NotesScope(notes_cx) = expect_context(cx);
+    let limits = expect_context::<RwSignal<NoteLimits>>(cx);
+
+    let (report, set_report) = create_signal(cx, None::<Result<ImportReport, String>>);
+
+    //notes parsed from keep or evernote, held until the preview is confirmed
+    let (pending_import, set_pending_import) = create_signal(cx, None::<Vec<SerializedNote>>);
+
+    let export = move || {
+        let export = notes.with(|notes| NotesExport::new(&user.get(), notes));
+        let filename = format!("notes-{}.json", TimeDate::default().format_date());
+
+        match serde_json::to_vec_pretty(&export) {
+            Ok(contents) => {
+                let _ = download_file(&filename, &contents, "application/json");
+            }
+            Err(e) => log!("failed to export notes: {}", e),
+        }
+    };
+
+    let import = move |e: ev::Event| {
+        let input = event_target::<web_sys::HtmlInputElement>(&e);
+        let Some(file) = input.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        input.set_value("");
+
+        let is_zip = file.name().ends_with(".zip");
+
+        spawn_local(async move {
+            let imported = if is_zip {
+                read_file_bytes(file).await
+                    .and_then(|bytes| crate::markdown_files::read_zip(&bytes, &user.get_untracked()))
+            }else{
+                read_file_text(file).await
+                    .and_then(|contents| NotesExport::parse(&contents))
+                    .map(|export| export.notes)
+            };
+
+            //markdown files only hold a note's contents, backups hold everything
+            let overwrite = if is_zip { Note::overwrite_contents_with } else { Note::overwrite_with };
+
+            let result = imported.map(|imported| {
+                let user = user.get_untracked();
+                let mut report = ImportReport::default();
+                set_notes.update(|notes| report = import_notes(notes_cx, imported, notes, &user, &limits.get_untracked(), overwrite));
+                report
+            });
+
+            set_report.set(Some(result));
+        });
+    };
+
+    let preview_foreign_import = move |e: ev::Event, is_enex: bool| {
+        let input = event_target::<web_sys::HtmlInputElement>(&e);
+        let Some(file) = input.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        input.set_value("");
+
+        spawn_local(async move {
+            let owner = user.get_untracked();
+            let imported = if is_enex {
+                read_file_text(file).await.and_then(|contents| crate::importers::parse_enex(&contents, &owner))
+            }else{
+                read_file_bytes(file).await.and_then(|bytes| crate::importers::parse_keep(&bytes, &owner))
+            };
+
+            match imported {
+                Ok(imported) => {
+                    let preview = notes.with_untracked(|notes| preview_import(&imported, notes, &owner, &limits.get_untracked()));
+                    set_pending_import.set(Some(imported));
+                    set_report.set(Some(Ok(preview)));
+                }
+                Err(e) => set_report.set(Some(Err(e))),
+            }
+        });
+    };
+
+    let confirm_import = move || {
+        let Some(imported) = pending_import.get() else {
+            return;
+        };
+        set_pending_import.set(None);
+
+        let user = user.get();
+        let mut report = ImportReport::default();
+        set_notes.update(|notes| report = import_notes(notes_cx, imported, notes, &user, &limits.get_untracked(), Note::overwrite_contents_with));
+        set_report.set(Some(Ok(report)));
+    };
+
+    let dismiss_report = move || {
+        set_pending_import.set(None);
+        set_report.set(None);
+    };
+
+    let export_markdown = move || {
+        let notes = notes.with(|notes| NotesExport::new(&user.get(), notes).notes);
+        let filename = format!("notes-{}.zip", TimeDate::default().format_date());
+
+        match crate::markdown_files::write_zip(&notes) {
+            Ok(contents) => {
+                let _ = download_file(&filename, &contents, "application/zip");
+            }
+            Err(e) => log!("failed to export notes: {}", e),
+        }
+    };
+
+    view!{
+        cx,
+        <div class="flex_seperator backup_controls">
+            <button class="reset share_btn" on:click=move |_| export()> "export notes" </button>
+            <button class="reset share_btn" on:click=move |_| export_markdown()> "export as markdown" </button>
+            <label class="reset share_btn">
+                "import notes"
+                <input type="file" accept="application/json,.json,application/zip,.zip" style="display: none" on:change=import/>
+            </label>
+            <label class="reset share_btn">
+                "import from google keep"
+                <input type="file" accept="application/json,.json,application/zip,.zip" style="display: none" on:change=move |e| preview_foreign_import(e, false)/>
+            </label>
+            <label class="reset share_btn">
+                "import from evernote"
+                <input type="file" accept=".enex" style="display: none" on:change=move |e| preview_foreign_import(e, true)/>
+            </label>
+
+            {move || report.get().map(|report| match report {
+                Ok(report) => view!{
+                    cx,
+                    <div class="import_report">
+                        {report.dry_run.then(|| view!{cx, <h1 class="reset small_details">"preview, nothing has been imported yet"</h1>})}
+                        <h1 class="reset small_details">{format!("{} created, {} updated, {} skipped", report.created.len(), report.updated.len(), report.skipped.len())}</h1>
+                        <ul class="reset">
+                            {report.created.into_iter().map(|title| view!{cx, <li class="small_details">"created "{title}</li>}).collect_view(cx)}
+                            {report.updated.into_iter().map(|title| view!{cx, <li class="small_details">"updated "{title}</li>}).collect_view(cx)}
+                            {report.skipped.into_iter().map(|(title, reason)| view!{cx, <li class="small_details">"skipped "{title}" ("{reason}")"</li>}).collect_view(cx)}
+                        </ul>
+                        {report.dry_run.then(|| view!{cx, <button class="reset share_btn" on:click=move |_| confirm_import()> "import" </button>})}
+                        <button class="reset share_btn" on:click=move |_| dismiss_report()> {if report.dry_run { "cancel" } else { "dismiss" }} </button>
+                    </div>
+                }.into_view(cx),
+                Err(e) => view!{cx, <h1 class="reset small_details">{e}</h1>}.into_view(cx),
+            })}
+        </div>
+    }
+}
+
+/// the current time, ticked forward every 30 seconds so overdue notes and reminders
+/// show up without a reload
+#[derive(Clone, Copy)]
+struct Clock(RwSignal<TimeDate>);
+
+/// the scope notes live in. views that come and go, like the backup controls, have to
+/// make notes in this scope so the notes' signals outlive the view
+#[derive(Clone, Copy)]
+struct NotesScope(Scope);
 
 #[component]
 fn NotesHome(cx: Scope, user: RwSignal<User>) -> impl IntoView {
@@ -877,23 +3507,73 @@ fn NotesHome(cx: Scope, user: RwSignal<User>) -> impl IntoView {
     let current_edit_focus = create_rw_signal(cx, None);
     let current_maximize_focus= create_rw_signal(cx, None);
 
-    let (only_pinned, set_only_pinned) = create_signal(cx, false);
+    let (only_pinned, _set_only_pinned) = create_signal(cx, false);
+    let (only_overdue, set_only_overdue) = create_signal(cx, false);
 
     provide_context(cx, set_notes);
+    provide_context(cx, notes);
+
+    let now = create_rw_signal(cx, TimeDate::default());
+    provide_context(cx, Clock(now));
+    provide_context(cx, NotesScope(cx));
+
+    //only refetch when the user actually changes, not every time their pinned list does
+    let user_id = create_memo(cx, move |_| user.with(|user| user.uuid));
+
+    create_effect(cx, move |_| {
+        user_id.track();
+        spawn_local(async move {
+            match list_notes(cx).await {
+                Ok(stored) => set_notes.set(stored.into_iter().map(|note| note.into_normal(cx)).collect()),
+                Err(e) => log!("failed to load notes: {}", e),
+            }
+        });
+    });
+
+    let templates = create_rw_signal(cx, Vec::<NoteTemplate>::new());
+    provide_context(cx, templates);
+
+    create_effect(cx, move |_| {
+        user_id.track();
+        spawn_local(async move {
+            match list_templates(cx).await {
+                Ok(list) => templates.set(list),
+                Err(e) => log!("failed to load templates: {}", e),
+            }
+        });
+    });
 
     let filter_mode= create_rw_signal(cx, NotesMode::Normal);
 
     let filtered_notes = Signal::derive(cx, move || {
-        notes.with(|notes| match filter_mode.get() {
-            NotesMode::Pinned => notes.iter().filter(|note| note.pinned.get()).cloned().collect::<Vec<_>>(),
-            NotesMode::Queried(query) => notes.iter().filter(|note| note.title.get().contains(&query)).cloned().collect::<Vec<_>>(),
-            NotesMode::PinnedQueried(query) => notes.iter().filter(|note| note.title.get().contains(&query) && note.pinned.get()).cloned().collect::<Vec<_>>(),
-            _ => notes.iter().cloned().collect::<Vec<_>>(),
+        let viewer = user.get();
+        let only_overdue = only_overdue.get();
+        notes.with(|notes| {
+            let now = now.get();
+            let visible = notes.iter().filter(|note| note.can_view(&viewer) && (!only_overdue || note.is_overdue(&now)));
+            match filter_mode.get() {
+                NotesMode::Pinned => visible.filter(|note| note.pinned.get()).cloned().collect::<Vec<_>>(),
+                NotesMode::Queried(query) => visible.filter(|note| note.title.get().contains(&query)).cloned().collect::<Vec<_>>(),
+                NotesMode::PinnedQueried(query) => visible.filter(|note| note.title.get().contains(&query) && note.pinned.get()).cloned().collect::<Vec<_>>(),
+                _ => visible.cloned().collect::<Vec<_>>(),
+            }
         })
     });
 
     let add_note = move || set_notes.update(|cur_notes| cur_notes.push(Note::new(cx, &user.get())));
 
+    let add_note_from_template = move |e: ev::Event| {
+        let select = event_target::<web_sys::HtmlSelectElement>(&e);
+        let id = Uuid::parse_str(&select.value()).ok();
+        select.set_value("");
+
+        let Some(template) = id.and_then(|id| templates.get().into_iter().find(|template| template.id == id)) else {
+            return;
+        };
+
+        set_notes.update(|cur_notes| cur_notes.push(Note::from_template(cx, &template, &user.get())));
+    };
+
     create_effect(cx, move |_| {
         if only_pinned.get() {
             match filter_mode.get_untracked() {
@@ -910,6 +3590,45 @@ fn NotesHome(cx: Scope, user: RwSignal<User>) -> impl IntoView {
         }
     });
 
+    let reminder_toast = create_rw_signal(cx, String::new());
+    let show_reminder_toast = create_rw_signal(cx, false);
+    let reminder_toast_handle = create_rw_signal(cx, None);
+
+    //re-runs on every tick of the clock
+    create_effect(cx, move |_| {
+        let now = now.get();
+        //read only viewers can't mark it sent, the owner's browser handles it
+        let due_notes = user.with_untracked(|user| notes.with_untracked(|notes| notes.iter().filter(|note| note.can_edit(user) && note.reminder_is_due(&now)).cloned().collect::<Vec<_>>()));
+
+        for note in due_notes {
+            let title = note.title.get_untracked();
+            let title = if title.is_empty() { "untitled note".to_owned() } else { title };
+
+            send_browser_notification("reminder", &title);
+            reminder_toast.set(format!("reminder: {}", title));
+            show_reminder_toast.set(true);
+
+            note.reminder_sent.set(true);
+            let (id, reminder) = (note.id, note.reminder.get_untracked());
+            spawn_local(async move {
+                if let Some(reminder) = reminder {
+                    if let Err(e) = mark_reminder_sent(cx, id, reminder).await {
+                        log!("failed to mark reminder sent: {}", e);
+                    }
+                }
+            });
+        }
+    });
+
+    //effects only run in the browser, so the clock never starts ticking during ssr
+    create_effect(cx, move |_| {
+        if let Ok(handle) = set_interval_with_handle(move || now.set(TimeDate::default()), core::time::Duration::from_secs(30)) {
+            on_cleanup(cx, move || handle.clear());
+        }
+    });
+
+    let reminder_toast_body = view!{cx, <div class="toast">{move || reminder_toast.get()}</div>}.into_view(cx);
+
     let query_notes = move |e: ev::Event| {
         let value = event_target_value(&e);
         if value.is_empty() {
@@ -929,7 +3648,8 @@ fn NotesHome(cx: Scope, user: RwSignal<User>) -> impl IntoView {
             <div class="spacer"/>
             <div class="handle_bar">
                 <div class="handle_bar_show"/>
-                <input type="text" class="reset note_search_bar" placeholder="search for notes..."  on:keydown=move |e| query_notes(e.into()) on:input= move |e| query_notes(e)/>
+                <input type="text" class="reset note_search_bar" placeholder="search for notes..."  on:keydown=move |e| query_notes(e.into()) on:input=query_notes/>
+                <A href="/settings" class="reset small_details">"settings"</A>
             </div>
 
             //<div>
@@ -937,8 +3657,28 @@ fn NotesHome(cx: Scope, user: RwSignal<User>) -> impl IntoView {
             //    <input type="checkbox" on:click=move|_| set_only_pinned.update(|pinned| *pinned = !*pinned)/>
             //</div>
 
+            <div class="reset small_details">
+                "only overdue notes "
+                <input type="checkbox" on:click=move |_| set_only_overdue.update(|overdue| *overdue = !*overdue)/>
+            </div>
+
+            <TimeoutNotifier body=reminder_toast_body show_state=show_reminder_toast timeout_duration=5000 timeout_handler=reminder_toast_handle/>
 
-            {move || (!user.get().guest).then_some(view!{cx, <div class="add_note_btn"><ImgBtn link="https://cdn-icons-png.flaticon.com/512/1828/1828925.png" on_click=move || add_note() /></div>})}
+            {move || (!user.get().guest).then(|| view!{cx, <BackupControls user/>})}
+
+
+            {move || (!user.get().guest).then_some(view!{cx, <div class="add_note_btn"><ImgBtn link="https://cdn-icons-png.flaticon.com/512/1828/1828925.png" on_click=add_note/></div>})}
+            {move || (!user.get().guest && !templates.with(Vec::is_empty)).then(|| view!{
+                cx,
+                <select class="reset template_picker" on:change=add_note_from_template>
+                    <option value="" selected=true>"new from template..."</option>
+                    <For
+                        each=move || templates.get()
+                        key=|template| template.id
+                        view=move |cx, template: NoteTemplate| view!{cx, <option value=template.id.to_string()>{template.name.clone()}</option>}
+                    />
+                </select>
+            })}
 
             //{move || (!user.get().guest).then_some(view!{cx, <button on:click=move |_| add_note()> "create new note" </button> })}
 
@@ -975,7 +3715,7 @@ enum MinimumNoteHeight {
 }
 
 impl MinimumNoteHeight {
-    fn into_px_height(&self) -> String {
+    fn to_px_height(&self) -> String {
         match *self {
             Self::Minimized => "85px".to_owned(),
             Self::Normal => "".to_owned(),
@@ -993,7 +3733,8 @@ fn state_into_height(is_minimized: bool) -> MinimumNoteHeight {
 
 #[component] 
 fn DisplayNote(cx: Scope, viewer: RwSignal<User>, note: Note, current_maximized: RwSignal<Option<Note>>, current_edit: RwSignal<Option<Note>>, write_notes: WriteSignal<Vec<Note>>) -> impl IntoView {
-    let (note, set_note) = create_signal(cx, note);
+    let (note, _set_note) = create_signal(cx, note);
+    let now = expect_context::<Clock>(cx).0;
 
     //should have variable min-height whether or not the note is minimized
 
@@ -1004,20 +3745,25 @@ fn DisplayNote(cx: Scope, viewer: RwSignal<User>, note: Note, current_maximized:
         set_min_note_height.set(state_into_height(note.get().minimized.get()))
     });
 
+    //the editor saves through this, so the status outlives the editor closing
+    let autosave = Autosave::new(cx);
+
 
     view!{
         cx,
-        <div class="note_body" style:min-height={min_note_height.get().into_px_height()} style:background-color=move || note.get().colour style:box-shadow=move || format!("5px 5px color-mix(in srgb, {} 50%, rgba(0, 0, 0, .75))", note.get().colour)>
+        <div id=move || format!("note-{}", note.get().id) class="note_body" class:overdue_note=move || note.get().is_overdue(&now.get()) style:min-height={min_note_height.get().to_px_height()} style:background-color=move || note.get().colour style:box-shadow=move || format!("5px 5px color-mix(in srgb, {} 50%, rgba(0, 0, 0, .75))", note.get().colour)>
+        <SaveStatus autosave note/>
         {move || if note.get().is_editing.get() {
             view!{cx,
-                <NoteEdit note viewer focused_view_note=current_maximized edit_focused_note=current_edit write_notes/>
+                <NoteEdit note viewer focused_view_note=current_maximized edit_focused_note=current_edit write_notes autosave/>
             }
         }else{
             view!{cx, 
                 <ViewNote note viewer focused_view_note=current_maximized write_notes/>
             }
         }}
-            {move || (viewer.get() == note.get().user && !note.get().minimized.get()).then_some(view!{cx, <EditButton edit_state=note.get().is_editing />})}
+            //while editing, leaving goes through the editor's save/cancel so buffered text isn't dropped
+            {move || (note.get().can_edit(&viewer.get()) && !note.get().minimized.get() && !note.get().is_editing.get()).then_some(view!{cx, <EditButton edit_state=note.get().is_editing />})}
         </div>
     }
 }
@@ -1043,3 +3789,75 @@ fn NotFound(cx: Scope) -> impl IntoView {
         <h1>"Not Found"</h1>
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wiki_links_keep_the_span_as_written() {
+        let body = "see [[ Foo ]] and [[bar]], not [[]] or [[un\nclosed";
+        let links = wiki_links(body);
+
+        assert_eq!(links.iter().map(|(_, label)| label.as_str()).collect::<Vec<_>>(), vec!["Foo", "bar"]);
+        assert_eq!(&body[links[0].0.clone()], "[[ Foo ]]");
+        assert_eq!(&body[links[1].0.clone()], "[[bar]]");
+    }
+
+    #[test]
+    fn wiki_links_render_with_padding_and_repeats() {
+        let runtime = create_runtime();
+        run_scope(runtime, |cx| {
+            let owner = SerializedUser::from(&User::new_user("someone".to_owned()));
+            let target = SerializedNote::new("Foo".to_owned(), String::new(), owner.clone()).into_normal(cx);
+            let note = SerializedNote::new(String::new(), "[[ foo ]], [[Foo]] and [[missing]]".to_owned(), owner).into_normal(cx);
+
+            let link = format!("[Foo](#note-{})", target.id);
+            assert_eq!(
+                render_wiki_links(&note, &[target, note.clone()]),
+                format!("{}, {} and [[missing]]", link, link)
+            );
+        });
+        runtime.dispose();
+    }
+
+    #[test]
+    fn restoring_edits_leaves_pins_and_comments_alone() {
+        let runtime = create_runtime();
+        run_scope(runtime, |cx| {
+            let user = User::new_user("someone".to_owned());
+            let note = SerializedNote::new("title".to_owned(), "body".to_owned(), SerializedUser::from(&user)).into_normal(cx);
+            let original = SerializedNote::from(&note);
+
+            note.update_title("changed".to_owned());
+            note.set_tags(vec!["work".to_owned()]);
+            note.toggle_pinned();
+            note.comments.set(vec![Comment {
+                id: Uuid::new_v4(),
+                author: SerializedUser::from(&user),
+                body: "hi".to_owned(),
+                date: TimeDate::default(),
+                last_edit: None,
+            }]);
+
+            note.restore_edits(original.clone());
+            assert_eq!(note.title.get_untracked(), "title");
+            assert!(note.tags.get_untracked().is_empty());
+            assert_eq!(note.last_edit.get_untracked(), original.last_edit);
+            assert!(note.pinned.get_untracked());
+            assert_eq!(note.comments.get_untracked().len(), 1);
+            assert!(same_edits(&SerializedNote::from(&note), &original));
+        });
+        runtime.dispose();
+    }
+
+    #[test]
+    fn counter_colour_steps_up_towards_the_limit() {
+        assert_eq!(set_color_from_state(0, 100).to_string(), "inherit");
+        assert_eq!(set_color_from_state(50, 100).to_string(), "inherit");
+        assert_eq!(set_color_from_state(51, 100).to_string(), "darkorange");
+        assert_eq!(set_color_from_state(99, 100).to_string(), "darkorange");
+        assert_eq!(set_color_from_state(100, 100).to_string(), "red");
+        assert_eq!(set_color_from_state(101, 100).to_string(), "darkred");
+    }
+}
@@ -0,0 +1,361 @@
+//! content addressed blob storage for note attachments, blobs are named after
+//! the sha256 of their contents so the same file uploaded twice is stored once
+//!
+//! only signed in users can upload. a blob is uploaded before the note pointing at
+//! it is saved, so blobs nothing points at are only deleted once they are older
+//! than `GRACE_PERIOD`, and `remove_orphans` sweeps up uploads whose note never was.
+//!
+//! blobs, and the png thumbnails made from image blobs, are only served to whoever
+//! uploaded them and to people who can see a note they're attached to.
+
+use crate::model::{Attachment, ALLOWED_ATTACHMENT_TYPES, MAX_ATTACHMENT_BYTES};
+use actix_files::NamedFile;
+use actix_web::{error, web, HttpRequest};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+static BLOB_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// how long an unreferenced blob is kept around for the note it was uploaded for
+const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// how often `remove_orphans` should run
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// thumbnails are cached in this subdirectory of the blob dir, as `<blob>.png`
+const THUMBNAIL_DIR: &str = "thumbnails";
+
+/// the longest side of a thumbnail, in pixels
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// images bigger than this either way aren't decoded, a small file can still
+/// claim to be enormous
+const MAX_IMAGE_SIDE: u32 = 10_000;
+
+/// sets (and creates if needed) the directory blobs are written to
+pub fn init(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    BLOB_DIR
+        .set(dir.to_owned())
+        .map_err(|_| io::Error::new(io::ErrorKind::AlreadyExists, "attachment directory already initialised"))
+}
+
+fn blob_dir() -> &'static Path {
+    BLOB_DIR.get_or_init(|| PathBuf::from("attachments"))
+}
+
+fn extension_for(content_type: &str) -> Option<&'static str> {
+    ALLOWED_ATTACHMENT_TYPES
+        .iter()
+        .find(|(allowed, _)| *allowed == content_type)
+        .map(|(_, extension)| *extension)
+}
+
+/// blob names are always `<hex digest>.<known extension>`, anything else could escape the blob dir
+fn is_valid_blob_name(blob: &str) -> bool {
+    match blob.split_once('.') {
+        Some((digest, extension)) => {
+            digest.len() == 64
+                && digest.bytes().all(|b| b.is_ascii_hexdigit())
+                && ALLOWED_ATTACHMENT_TYPES.iter().any(|(_, allowed)| *allowed == extension)
+        }
+        None => false,
+    }
+}
+
+fn is_image(blob: &str) -> bool {
+    ALLOWED_ATTACHMENT_TYPES
+        .iter()
+        .any(|(content_type, extension)| content_type.starts_with("image/") && blob.ends_with(&format!(".{}", extension)))
+}
+
+fn thumbnail_path(dir: &Path, blob: &str) -> PathBuf {
+    dir.join(THUMBNAIL_DIR).join(format!("{}.png", blob))
+}
+
+/// a blob the caller isn't allowed to see looks the same as one that doesn't exist
+fn check_access(req: &HttpRequest, blob: &str) -> actix_web::Result<()> {
+    let allowed = is_valid_blob_name(blob)
+        && crate::sessions::request_user(req).is_some_and(|viewer| crate::store::read(|store| store.can_view_blob(blob, viewer.uuid)));
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(error::ErrorNotFound("no such attachment"))
+    }
+}
+
+async fn upload(req: HttpRequest, body: web::Bytes) -> actix_web::Result<web::Json<Attachment>> {
+    let Some(user) = crate::sessions::request_user(&req) else {
+        return Err(error::ErrorUnauthorized("sign in to upload attachments"));
+    };
+
+    let content_type = req
+        .headers()
+        .get("Content-Type")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let Some(extension) = extension_for(content_type) else {
+        return Err(error::ErrorUnsupportedMediaType(format!("{} attachments are not allowed", content_type)));
+    };
+
+    if body.len() as u64 > MAX_ATTACHMENT_BYTES {
+        return Err(error::ErrorPayloadTooLarge("attachment is too large"));
+    }
+
+    let name = req
+        .headers()
+        .get("X-File-Name")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("attachment")
+        .to_owned();
+
+    let size = body.len() as u64;
+    let blob = format!("{:x}.{}", Sha256::digest(&body), extension);
+    let path = blob_dir().join(&blob);
+
+    //an existing blob gets its clock reset, so the grace period covers this upload too
+    web::block(move || match fs::File::options().write(true).open(&path) {
+        Ok(file) => file.set_modified(SystemTime::now()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => fs::write(&path, body),
+        Err(e) => Err(e),
+    })
+    .await?
+    .map_err(error::ErrorInternalServerError)?;
+
+    let uploaded = blob.clone();
+    crate::store::write(move |store| store.uploads.entry(uploaded).or_default().insert(user.uuid))
+        .map_err(error::ErrorInternalServerError)?;
+
+    Ok(web::Json(Attachment {
+        id: Uuid::new_v4(),
+        name,
+        content_type: content_type.to_owned(),
+        size,
+        blob,
+    }))
+}
+
+async fn serve(req: HttpRequest, blob: web::Path<String>) -> actix_web::Result<NamedFile> {
+    let blob = blob.into_inner();
+    check_access(&req, &blob)?;
+
+    Ok(NamedFile::open(blob_dir().join(blob))?)
+}
+
+/// scales the image at `source` down to fit in `THUMBNAIL_SIZE` and writes it to `dest` as a png
+fn make_thumbnail(source: &Path, dest: &Path) -> io::Result<()> {
+    let invalid = |e: image::ImageError| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIDE);
+    limits.max_image_height = Some(MAX_IMAGE_SIDE);
+
+    let mut reader = image::ImageReader::new(Cursor::new(fs::read(source)?)).with_guessed_format()?;
+    reader.limits(limits);
+    let thumbnail = reader.decode().map_err(invalid)?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+
+    let mut png = Cursor::new(Vec::new());
+    thumbnail.write_to(&mut png, image::ImageFormat::Png).map_err(invalid)?;
+
+    //written next to it and renamed so a request racing this one never sees half a file
+    fs::create_dir_all(dest.parent().unwrap_or(dest))?;
+    let partial = dest.with_extension(format!("{}.partial", Uuid::new_v4().simple()));
+    fs::write(&partial, png.into_inner())?;
+    fs::rename(&partial, dest)
+}
+
+async fn serve_thumbnail(req: HttpRequest, blob: web::Path<String>) -> actix_web::Result<NamedFile> {
+    let blob = blob.into_inner();
+    check_access(&req, &blob)?;
+
+    if !is_image(&blob) {
+        return Err(error::ErrorNotFound("only images have thumbnails"));
+    }
+
+    let path = thumbnail_path(blob_dir(), &blob);
+    if !path.exists() {
+        let (source, dest) = (blob_dir().join(&blob), path.clone());
+        web::block(move || make_thumbnail(&source, &dest))
+            .await?
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => error::ErrorNotFound("no such attachment"),
+                _ => error::ErrorUnprocessableEntity(format!("couldn't make a thumbnail: {}", e)),
+            })?;
+    }
+
+    Ok(NamedFile::open(path)?)
+}
+
+/// routes for uploading and fetching attachments, mounted at `/attachments`
+pub fn service() -> actix_web::Scope {
+    web::scope("/attachments")
+        .app_data(web::PayloadConfig::new(MAX_ATTACHMENT_BYTES as usize))
+        .route("", web::post().to(upload))
+        .route("/{blob}", web::get().to(serve))
+        .route("/{blob}/thumbnail", web::get().to(serve_thumbnail))
+}
+
+fn is_past_grace_period(path: &Path, now: SystemTime) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| now.duration_since(modified).ok())
+        .is_some_and(|age| age >= GRACE_PERIOD)
+}
+
+fn remove_if_unreferenced(dir: &Path, blob: &str, referenced: &HashSet<&str>, now: SystemTime) {
+    let path = dir.join(blob);
+    if referenced.contains(blob) || !is_valid_blob_name(blob) || !is_past_grace_period(&path, now) {
+        return;
+    }
+
+    for path in [path, thumbnail_path(dir, blob)] {
+        if let Err(e) = fs::remove_file(path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                leptos::warn!("failed to remove attachment {}: {}", blob, e);
+            }
+        }
+    }
+}
+
+/// deletes every candidate blob that is no longer referenced by any note
+pub fn remove_unreferenced<'a>(candidates: impl IntoIterator<Item = &'a str>, referenced: &HashSet<&str>) {
+    let now = SystemTime::now();
+    for blob in candidates {
+        remove_if_unreferenced(blob_dir(), blob, referenced, now);
+    }
+}
+
+fn remove_orphans_in(dir: &Path, referenced: &HashSet<&str>, now: SystemTime) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        if let Some(blob) = name.to_str() {
+            remove_if_unreferenced(dir, blob, referenced, now);
+        }
+    }
+
+    Ok(())
+}
+
+/// deletes every blob in the upload directory that no note points at, catching
+/// uploads whose note was never saved
+pub fn remove_orphans() -> io::Result<()> {
+    crate::store::read(|store| remove_orphans_in(blob_dir(), &store.referenced_blobs(), SystemTime::now()))?;
+
+    //uploaders only need their own access until a note points at the blob
+    let settled = crate::store::read(|store| {
+        let referenced = store.referenced_blobs();
+        store
+            .uploads
+            .keys()
+            .filter(|blob| referenced.contains(blob.as_str()) || !blob_dir().join(blob).exists())
+            .cloned()
+            .collect::<Vec<_>>()
+    });
+
+    if !settled.is_empty() {
+        crate::store::write(|store| {
+            for blob in &settled {
+                store.uploads.remove(blob);
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blob(byte: char) -> String {
+        format!("{}.png", byte.to_string().repeat(64))
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("notes-attachments-{}-{}", name, Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn blob_names_must_be_digests_with_known_extensions() {
+        assert!(is_valid_blob_name(&blob('a')));
+        assert!(!is_valid_blob_name("../notes_db.json"));
+        assert!(!is_valid_blob_name(&format!("{}.exe", "a".repeat(64))));
+        assert!(!is_valid_blob_name("abc.png"));
+    }
+
+    #[test]
+    fn orphans_are_removed_once_past_the_grace_period() {
+        let dir = temp_dir("orphans");
+        for name in [blob('a'), blob('b'), "notes.txt".to_owned()] {
+            fs::write(dir.join(name), b"contents").unwrap();
+        }
+
+        let kept = blob('a');
+        let referenced = HashSet::from([kept.as_str()]);
+
+        remove_orphans_in(&dir, &referenced, SystemTime::now()).unwrap();
+        assert!(dir.join(blob('b')).exists(), "new uploads are kept");
+
+        remove_orphans_in(&dir, &referenced, SystemTime::now() + GRACE_PERIOD).unwrap();
+        assert!(dir.join(blob('a')).exists(), "referenced blobs are kept");
+        assert!(!dir.join(blob('b')).exists(), "old orphans are removed");
+        assert!(dir.join("notes.txt").exists(), "files that aren't blobs are left alone");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn thumbnails_fit_in_the_thumbnail_size() {
+        let dir = temp_dir("thumbnails");
+        let source = dir.join(blob('c'));
+        image::RgbImage::new(1024, 512).save_with_format(&source, image::ImageFormat::Png).unwrap();
+
+        let dest = thumbnail_path(&dir, &blob('c'));
+        make_thumbnail(&source, &dest).unwrap();
+        let thumbnail = image::open(&dest).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2));
+
+        //swept along with its blob
+        remove_orphans_in(&dir, &HashSet::new(), SystemTime::now() + GRACE_PERIOD).unwrap();
+        assert!(!source.exists() && !dest.exists());
+
+        fs::write(&source, b"not really a png").unwrap();
+        assert_eq!(make_thumbnail(&source, &dest).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn blobs_are_only_visible_through_notes_or_uploads() {
+        let user = |name: &str| crate::model::SerializedUser { username: name.to_owned(), creation_date: crate::model::TimeDate::default(), uuid: Uuid::new_v4() };
+        let (owner, reader, stranger, uploader) = (user("owner"), user("reader"), user("stranger"), user("uploader"));
+
+        let attachment = Attachment { id: Uuid::new_v4(), name: "photo.png".to_owned(), content_type: "image/png".to_owned(), size: 1, blob: blob('d') };
+        let mut note = crate::model::SerializedNote::new("photos".to_owned(), String::new(), owner.clone());
+        note.attachments.push(attachment);
+        note.share_with(reader.clone(), crate::model::AccessLevel::Read);
+
+        let mut store = crate::store::NoteStore::default();
+        store.notes.insert(note.id, note);
+        store.uploads.insert(blob('e'), HashSet::from([uploader.uuid]));
+
+        assert!(store.can_view_blob(&blob('d'), owner.uuid));
+        assert!(store.can_view_blob(&blob('d'), reader.uuid));
+        assert!(!store.can_view_blob(&blob('d'), stranger.uuid));
+        assert!(store.can_view_blob(&blob('e'), uploader.uuid));
+        assert!(!store.can_view_blob(&blob('e'), owner.uuid));
+    }
+}
@@ -0,0 +1,249 @@
+//! command line client for the notes api
+//!
+//! needs `NOTES_SERVER` (defaults to http://127.0.0.1:3000) and `NOTES_TOKEN`,
+//! a personal access token from the settings page, or the matching flags
+
+use clap::{Parser, Subcommand};
+use leptos_start::model::{NoteKind, NotesExport, SerializedNote, SerializedUser, TimeDate, EXPORT_VERSION};
+use serde_json::json;
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    process::{self, Command},
+};
+
+#[derive(Parser)]
+#[command(name = "notes", about = "manage notes from the terminal")]
+struct Cli {
+    #[arg(long, env = "NOTES_SERVER", default_value = "http://127.0.0.1:3000")]
+    server: String,
+    #[arg(long, env = "NOTES_TOKEN", hide_env_values = true)]
+    token: String,
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// list your notes
+    List {
+        #[arg(long)]
+        pinned: bool,
+        #[arg(long)]
+        tag: Option<String>,
+        #[arg(long)]
+        overdue: bool,
+    },
+    /// create a note, `--body -` reads the body from stdin
+    Add {
+        #[arg(long)]
+        title: String,
+        #[arg(long, default_value = "")]
+        body: String,
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        #[arg(long)]
+        pinned: bool,
+    },
+    /// edit a note's title and body in $EDITOR
+    Edit { id: String },
+    /// search titles and bodies
+    Search { query: String },
+    /// write every note you own as a json backup the web ui can import
+    Export {
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+}
+
+struct Client {
+    server: String,
+    token: String,
+    http: reqwest::blocking::Client,
+}
+
+impl Client {
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::blocking::RequestBuilder {
+        self.http
+            .request(method, format!("{}/v1{}", self.server.trim_end_matches('/'), path))
+            .bearer_auth(&self.token)
+    }
+
+    fn send<T: serde::de::DeserializeOwned>(&self, request: reqwest::blocking::RequestBuilder) -> Result<T, String> {
+        let response = request.send().map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.json::<serde_json::Value>().ok();
+            let error = body
+                .as_ref()
+                .and_then(|body| body.get("error"))
+                .and_then(|error| error.as_str())
+                .unwrap_or_else(|| status.canonical_reason().unwrap_or("request failed"));
+            return Err(format!("{}: {}", status.as_u16(), error));
+        }
+
+        response.json::<T>().map_err(|e| e.to_string())
+    }
+
+    fn list(&self, query: &[(&str, String)]) -> Result<Vec<SerializedNote>, String> {
+        self.send(self.request(reqwest::Method::GET, "/notes").query(query))
+    }
+}
+
+/// one line per note, using the same date formatting as the web ui
+fn print_note(note: &SerializedNote) {
+    let title: &str = if note.title.is_empty() { "untitled note" } else { &note.title };
+    let pin = if note.pinned { "* " } else { "  " };
+
+    let mut details = vec![note.date.format_datetime(), format!("@{}", note.user.username)];
+
+    if note.kind == NoteKind::Checklist {
+        let done = note.items.iter().filter(|item| item.done).count();
+        details.push(format!("{}/{} done", done, note.items.len()));
+    }
+    if let Some(due) = &note.due {
+        details.push(format!("due {}", due.format_datetime()));
+    }
+    if !note.tags.is_empty() {
+        details.push(note.tags.iter().map(|tag| format!("#{}", tag)).collect::<Vec<_>>().join(" "));
+    }
+
+    println!("{}{}  {}  ({})", pin, note.id, title, details.join(", "));
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut body = String::new();
+    io::stdin().read_to_string(&mut body).map_err(|e| e.to_string())?;
+    Ok(body)
+}
+
+/// the first line of the file is the title, everything after the blank line is the body
+fn editor_text(note: &SerializedNote) -> String {
+    format!("{}\n\n{}", note.title, note.body)
+}
+
+/// splits the editor's file back into a title and a body, trimming the blank
+/// lines and trailing whitespace editors tend to leave behind
+fn parse_editor_text(contents: &str) -> (String, String) {
+    let (title, body) = contents.split_once('\n').unwrap_or((contents, ""));
+    (title.trim().to_owned(), body.trim_start_matches('\n').trim_end().to_owned())
+}
+
+/// opens the note in `$VISUAL` or `$EDITOR`, returning the edited title and
+/// body, or None if nothing changed
+fn edit_in_editor(note: &SerializedNote) -> Result<Option<(String, String)>, String> {
+    let editor = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| "vi".to_owned());
+    let path = env::temp_dir().join(format!("note-{}.md", note.id));
+    let original = editor_text(note);
+
+    fs::write(&path, &original).map_err(|e| e.to_string())?;
+
+    let status = Command::new(&editor).arg(&path).status().map_err(|e| format!("couldn't run {}: {}", editor, e))?;
+    let contents = fs::read_to_string(&path).map_err(|e| e.to_string());
+    let _ = fs::remove_file(&path);
+
+    if !status.success() {
+        return Err(format!("{} exited with {}", editor, status));
+    }
+
+    let edited = parse_editor_text(&contents?);
+    Ok((edited != parse_editor_text(&original)).then_some(edited))
+}
+
+fn run(cli: Cli) -> Result<(), String> {
+    let client = Client {
+        server: cli.server,
+        token: cli.token,
+        http: reqwest::blocking::Client::new(),
+    };
+
+    match cli.command {
+        Cmd::List { pinned, tag, overdue } => {
+            let mut query = Vec::new();
+            if pinned {
+                query.push(("pinned", "true".to_owned()));
+            }
+            if overdue {
+                query.push(("overdue", "true".to_owned()));
+            }
+            if let Some(tag) = tag {
+                query.push(("tag", tag));
+            }
+
+            client.list(&query)?.iter().for_each(print_note);
+        }
+        Cmd::Add { title, body, tags, pinned } => {
+            let body = if body == "-" { read_stdin()? } else { body };
+            let note = client.send::<SerializedNote>(
+                client
+                    .request(reqwest::Method::POST, "/notes")
+                    .json(&json!({ "title": title, "body": body, "tags": tags, "pinned": pinned })),
+            )?;
+            print_note(&note);
+        }
+        Cmd::Edit { id } => {
+            let note = client.send::<SerializedNote>(client.request(reqwest::Method::GET, &format!("/notes/{}", id)))?;
+            let Some((title, body)) = edit_in_editor(&note)? else {
+                println!("no changes");
+                return Ok(());
+            };
+
+            let note = client.send::<SerializedNote>(
+                client
+                    .request(reqwest::Method::PATCH, &format!("/notes/{}", id))
+                    .json(&json!({ "title": title, "body": body })),
+            )?;
+            print_note(&note);
+        }
+        Cmd::Search { query } => {
+            client.list(&[("q", query)])?.iter().for_each(print_note);
+        }
+        Cmd::Export { output } => {
+            let user = client.send::<SerializedUser>(client.request(reqwest::Method::GET, "/me"))?;
+            let mut notes = client.list(&[])?;
+            notes.retain(|note| note.user.uuid == user.uuid);
+
+            let export = NotesExport {
+                version: EXPORT_VERSION,
+                exported: TimeDate::default(),
+                user,
+                notes,
+            };
+            let contents = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+
+            match output {
+                Some(path) => fs::write(&path, contents).map_err(|e| format!("{}: {}", path, e))?,
+                None => io::stdout().write_all(contents.as_bytes()).map_err(|e| e.to_string())?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("notes: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unedited_text_parses_the_same_as_the_original() {
+        let user = SerializedUser {
+            username: "ann".to_owned(),
+            creation_date: TimeDate::default(),
+            uuid: uuid::Uuid::new_v4(),
+        };
+        let note = SerializedNote::new("title ".to_owned(), "body with trailing space  \n".to_owned(), user);
+
+        let text = editor_text(&note);
+        assert_eq!(parse_editor_text(&text), parse_editor_text(&format!("{}\n", text)));
+        assert_eq!(parse_editor_text(&text), ("title".to_owned(), "body with trailing space".to_owned()));
+    }
+}
@@ -0,0 +1,236 @@
+//! icalendar subscription of every note with a due date or reminder
+//!
+//! calendar apps can't send headers, so the feed lives behind an unguessable
+//! per-user link (`GET /calendar/{token}.ics`) made from the settings page.
+//! making a new link revokes the old one.
+//!
+//! notes are sent as VEVENTs at their due date (or reminder if they have no due
+//! date) since that's all most calendar subscriptions show, `?todo=true` sends
+//! deadlines as VTODOs instead for apps with a task list. reminders on a note
+//! with a due date become a VALARM.
+//!
+//! links carry the subscriber's time zone (`?tz=Europe/London`), start and due
+//! times are sent as wall clock times in it with a TZID so recurring notes stay
+//! at the same time of day across a dst change, like they do in the app. the
+//! TZID is the iana name rather than a VTIMEZONE, which calendar apps resolve
+//! themselves. links made before time zones were added get utc times.
+
+use crate::model::{SerializedNote, TimeDate};
+use crate::store;
+use actix_web::{error, web, HttpResponse};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+/// rfc 5545 wants lines folded at 75 octets, continuation lines start with a space
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+
+    out.push_str("\r\n");
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// a DATE-TIME property in the subscriber's time zone, or in utc if there isn't one
+fn time_property(name: &str, time: &TimeDate, tz: Option<Tz>) -> String {
+    match tz {
+        Some(tz) => format!("{};TZID={}:{}", name, tz.name(), time.to_naive_in(&tz).format("%Y%m%dT%H%M%S")),
+        None => format!("{}:{}", name, time.to_ical()),
+    }
+}
+
+fn push_note(out: &mut String, note: &SerializedNote, as_todo: bool, tz: Option<Tz>, stamp: &str) {
+    let (start, alarm) = match (&note.due, &note.reminder) {
+        (Some(due), reminder) => (due, reminder.as_ref()),
+        (None, Some(reminder)) => (reminder, None),
+        (None, None) => return,
+    };
+    let component = if as_todo && note.due.is_some() { "VTODO" } else { "VEVENT" };
+
+    let title = if note.title.is_empty() { "untitled note" } else { &note.title };
+
+    push_line(out, &format!("BEGIN:{}", component));
+    push_line(out, &format!("UID:{}@notes", note.id));
+    push_line(out, &format!("DTSTAMP:{}", stamp));
+    push_line(out, &time_property("DTSTART", start, tz));
+    if component == "VTODO" {
+        push_line(out, &time_property("DUE", start, tz));
+    }
+    if let Some(last_edit) = &note.last_edit {
+        push_line(out, &format!("LAST-MODIFIED:{}", last_edit.to_ical()));
+    }
+    push_line(out, &format!("SUMMARY:{}", escape_text(title)));
+    if !note.body.is_empty() {
+        push_line(out, &format!("DESCRIPTION:{}", escape_text(&note.body)));
+    }
+    if !note.tags.is_empty() {
+        let tags = note.tags.iter().map(|tag| escape_text(tag)).collect::<Vec<_>>();
+        push_line(out, &format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if let Some(recurrence) = &note.recurrence {
+        let start_date = match tz {
+            Some(tz) => start.to_naive_in(&tz).date(),
+            None => start.to_naive_in(&chrono::Utc).date(),
+        };
+        push_line(out, &format!("RRULE:{}", recurrence.to_rrule(start_date)));
+    }
+
+    if let Some(alarm) = alarm {
+        push_line(out, "BEGIN:VALARM");
+        push_line(out, "ACTION:DISPLAY");
+        push_line(out, &format!("DESCRIPTION:{}", escape_text(title)));
+        push_line(out, &format!("TRIGGER;VALUE=DATE-TIME:{}", alarm.to_ical()));
+        push_line(out, "END:VALARM");
+    }
+
+    push_line(out, &format!("END:{}", component));
+}
+
+#[derive(Deserialize)]
+struct CalendarOptions {
+    #[serde(default)]
+    todo: bool,
+    tz: Option<String>,
+}
+
+async fn calendar(token: web::Path<String>, options: web::Query<CalendarOptions>) -> actix_web::Result<HttpResponse> {
+    let tz = options.tz.as_deref()
+        .map(|tz| tz.parse::<Tz>().map_err(|_| error::ErrorBadRequest("unknown time zone")))
+        .transpose()?;
+    let token = token.strip_suffix(".ics").unwrap_or(&token);
+    let hash = crate::api::hash_token(token);
+
+    let notes = store::read(|store| {
+        let user = *store.calendar_tokens.get(&hash)?;
+
+        let mut notes = store
+            .notes
+            .values()
+            .filter(|note| note.due.is_some() || note.reminder.is_some())
+            .filter(|note| note.access_level(user).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        notes.sort_by(|a, b| a.date.cmp(&b.date));
+        Some(notes)
+    })
+    .ok_or_else(|| error::ErrorNotFound("no such calendar"))?;
+
+    let stamp = TimeDate::default().to_ical();
+
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//notes//calendar//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, "X-WR-CALNAME:notes");
+
+    for note in &notes {
+        push_note(&mut out, note, options.todo, tz, &stamp);
+    }
+
+    push_line(&mut out, "END:VCALENDAR");
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(out))
+}
+
+/// the path a calendar app should subscribe to for `token`, with times in `tz`
+pub fn path(token: &str, tz: Tz) -> String {
+    let tz = url::form_urlencoded::byte_serialize(tz.name().as_bytes()).collect::<String>();
+    format!("/calendar/{}.ics?tz={}", token, tz)
+}
+
+pub fn service() -> actix_web::Scope {
+    web::scope("/calendar").route("/{token}", web::get().to(calendar))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Recurrence, RecurrenceUnit, SerializedUser};
+    use chrono_tz::Europe::London;
+    use uuid::Uuid;
+
+    fn note(due: TimeDate, recurrence: Option<Recurrence>) -> SerializedNote {
+        let user = SerializedUser {
+            username: "someone".to_owned(),
+            creation_date: TimeDate::default(),
+            uuid: Uuid::new_v4(),
+        };
+
+        SerializedNote {
+            due: Some(due),
+            recurrence,
+            ..SerializedNote::new("rent".to_owned(), String::new(), user)
+        }
+    }
+
+    fn london(month: u32, day: u32, hour: u32) -> TimeDate {
+        let naive = chrono::NaiveDate::from_ymd_opt(2024, month, day).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+        let local = chrono::TimeZone::from_local_datetime(&London, &naive).unwrap();
+        TimeDate::from_epoch(local.timestamp())
+    }
+
+    fn lines(note: &SerializedNote, tz: Option<Tz>) -> Vec<String> {
+        let mut out = String::new();
+        push_note(&mut out, note, false, tz, "20240101T000000Z");
+        out.split("\r\n").map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn start_is_local_time_with_a_tzid() {
+        let lines = lines(&note(london(7, 1, 9), Some(Recurrence::Daily)), Some(London));
+
+        assert!(lines.contains(&"DTSTART;TZID=Europe/London:20240701T090000".to_owned()));
+        assert!(lines.contains(&"RRULE:FREQ=DAILY".to_owned()));
+    }
+
+    #[test]
+    fn start_is_utc_without_a_time_zone() {
+        let lines = lines(&note(london(7, 1, 9), None), None);
+
+        assert!(lines.contains(&"DTSTART:20240701T080000Z".to_owned()));
+    }
+
+    #[test]
+    fn monthly_repeats_from_the_31st_keep_short_months() {
+        let every = Recurrence::Every { interval: 1, unit: RecurrenceUnit::Months, day: Some(31) };
+        let lines = lines(&note(london(1, 31, 9), Some(every)), Some(London));
+
+        assert!(lines.contains(&"RRULE:FREQ=MONTHLY;INTERVAL=1;BYMONTHDAY=28,29,30,31;BYSETPOS=-1".to_owned()));
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let mut out = String::new();
+        push_line(&mut out, &"a".repeat(100));
+
+        assert_eq!(out, format!("{}\r\n {}\r\n", "a".repeat(75), "a".repeat(25)));
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape_text("a,b;c\\d\ne"), "a\\,b\\;c\\\\d\\ne");
+    }
+
+    #[test]
+    fn links_encode_the_time_zone() {
+        assert_eq!(path("abc", chrono_tz::Etc::GMTPlus5), "/calendar/abc.ics?tz=Etc%2FGMT%2B5");
+    }
+}
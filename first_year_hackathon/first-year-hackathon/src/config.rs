@@ -0,0 +1,296 @@
+//! deployment settings, read once at startup
+//!
+//! settings come from `notes.toml` (or the file named by `NOTES_CONFIG`), any of
+//! them can be overridden with a `NOTES_*` environment variable and anything left
+//! out falls back to its default. a missing `notes.toml` is fine, a missing file
+//! that was asked for with `NOTES_CONFIG` is not.
+//!
+//! ```toml
+//! database_path = "notes_db.json"     # NOTES_DATABASE_PATH
+//! upload_dir = "attachments"          # NOTES_UPLOAD_DIR
+//! session_lifetime_days = 30          # NOTES_SESSION_LIFETIME_DAYS, unset means tokens never expire
+//! title_char_limit = 100              # NOTES_TITLE_CHAR_LIMIT
+//! body_char_limit = 1000              # NOTES_BODY_CHAR_LIMIT
+//! registration_open = true            # NOTES_REGISTRATION_OPEN
+//! log_level = "info"                  # NOTES_LOG_LEVEL
+//! bind_address = "127.0.0.1:3000"     # NOTES_BIND_ADDRESS, defaults to leptos' site-addr
+//! public_url = "https://notes.example" # NOTES_PUBLIC_URL, where people reach the server, used for links in feeds
+//! ```
+
+use crate::model::NoteLimits;
+use serde::Deserialize;
+use std::{
+    env, fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+const DEFAULT_PATH: &str = "notes.toml";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Env(&'static str, String),
+    Invalid(&'static str, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            Self::Parse(path, e) => write!(f, "{} isn't valid: {}", path.display(), e),
+            Self::Env(var, reason) => write!(f, "environment variable {} {}", var, reason),
+            Self::Invalid(setting, reason) => write!(f, "`{}` {}", setting, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database_path: PathBuf,
+    pub upload_dir: PathBuf,
+    /// access tokens that go unused for this long stop working
+    pub session_lifetime_days: Option<u32>,
+    pub title_char_limit: u64,
+    pub body_char_limit: u64,
+    /// whether names that haven't been seen before get an account
+    pub registration_open: bool,
+    pub log_level: String,
+    pub bind_address: Option<SocketAddr>,
+    /// the address links handed out of the app point at, the request's own
+    /// host header can't be trusted for this
+    pub public_url: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            database_path: PathBuf::from("notes_db.json"),
+            upload_dir: PathBuf::from("attachments"),
+            session_lifetime_days: None,
+            title_char_limit: NoteLimits::default().title,
+            body_char_limit: NoteLimits::default().body,
+            registration_open: true,
+            log_level: "info".to_owned(),
+            bind_address: None,
+            public_url: "http://127.0.0.1:3000".to_owned(),
+        }
+    }
+}
+
+fn env_var<T: FromStr>(lookup: &impl Fn(&'static str) -> Result<String, env::VarError>, var: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    match lookup(var) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| ConfigError::Env(var, format!("is invalid: {}", e))),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(ConfigError::Env(var, "isn't valid unicode".to_owned())),
+    }
+}
+
+impl Config {
+    fn from_file(path: &Path, required: bool) -> Result<Self, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_owned(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(Self::default()),
+            Err(e) => Err(ConfigError::Read(path.to_owned(), e)),
+        }
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        self.apply_vars(env::var)
+    }
+
+    /// `apply_env` with the variables looked up through `lookup`, so tests don't have to touch the real environment
+    fn apply_vars(&mut self, lookup: impl Fn(&'static str) -> Result<String, env::VarError>) -> Result<(), ConfigError> {
+        if let Some(path) = env_var(&lookup, "NOTES_DATABASE_PATH")? {
+            self.database_path = path;
+        }
+        if let Some(dir) = env_var(&lookup, "NOTES_UPLOAD_DIR")? {
+            self.upload_dir = dir;
+        }
+        if let Some(days) = env_var(&lookup, "NOTES_SESSION_LIFETIME_DAYS")? {
+            self.session_lifetime_days = Some(days);
+        }
+        if let Some(limit) = env_var(&lookup, "NOTES_TITLE_CHAR_LIMIT")? {
+            self.title_char_limit = limit;
+        }
+        if let Some(limit) = env_var(&lookup, "NOTES_BODY_CHAR_LIMIT")? {
+            self.body_char_limit = limit;
+        }
+        if let Some(open) = env_var(&lookup, "NOTES_REGISTRATION_OPEN")? {
+            self.registration_open = open;
+        }
+        if let Some(level) = env_var(&lookup, "NOTES_LOG_LEVEL")? {
+            self.log_level = level;
+        }
+        if let Some(addr) = env_var(&lookup, "NOTES_BIND_ADDRESS")? {
+            self.bind_address = Some(addr);
+        }
+        if let Some(url) = env_var(&lookup, "NOTES_PUBLIC_URL")? {
+            self.public_url = url;
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.database_path.as_os_str().is_empty() {
+            return Err(ConfigError::Invalid("database_path", "can't be empty".to_owned()));
+        }
+        if self.database_path.is_dir() {
+            return Err(ConfigError::Invalid("database_path", format!("{} is a directory", self.database_path.display())));
+        }
+        if self.upload_dir.as_os_str().is_empty() {
+            return Err(ConfigError::Invalid("upload_dir", "can't be empty".to_owned()));
+        }
+        if self.upload_dir.is_file() {
+            return Err(ConfigError::Invalid("upload_dir", format!("{} is a file", self.upload_dir.display())));
+        }
+        if self.session_lifetime_days == Some(0) {
+            return Err(ConfigError::Invalid("session_lifetime_days", "must be at least 1, leave it out for tokens that never expire".to_owned()));
+        }
+        if self.title_char_limit == 0 {
+            return Err(ConfigError::Invalid("title_char_limit", "must be at least 1".to_owned()));
+        }
+        if self.body_char_limit == 0 {
+            return Err(ConfigError::Invalid("body_char_limit", "must be at least 1".to_owned()));
+        }
+        if !(self.public_url.starts_with("http://") || self.public_url.starts_with("https://")) {
+            return Err(ConfigError::Invalid("public_url", format!("{:?} must start with http:// or https://", self.public_url)));
+        }
+        if self.log_level().is_none() {
+            return Err(ConfigError::Invalid("log_level", format!("{:?} isn't one of error, warn, info, debug or trace", self.log_level)));
+        }
+
+        Ok(())
+    }
+
+    pub fn note_limits(&self) -> NoteLimits {
+        NoteLimits {
+            title: self.title_char_limit,
+            body: self.body_char_limit,
+        }
+    }
+
+    pub fn log_level(&self) -> Option<log::Level> {
+        log::Level::from_str(&self.log_level).ok()
+    }
+
+    /// the public url without a trailing slash, so paths can be appended to it
+    pub fn public_url(&self) -> &str {
+        self.public_url.trim_end_matches('/')
+    }
+
+    /// how long an access token can sit unused, in seconds
+    pub fn session_lifetime(&self) -> Option<i64> {
+        self.session_lifetime_days.map(|days| days as i64 * 24 * 60 * 60)
+    }
+}
+
+/// reads, overrides and validates the config, then makes it available through [`get`]
+pub fn load() -> Result<&'static Config, ConfigError> {
+    let (path, required) = match env::var_os("NOTES_CONFIG") {
+        Some(path) => (PathBuf::from(path), true),
+        None => (PathBuf::from(DEFAULT_PATH), false),
+    };
+
+    let mut config = Config::from_file(&path, required)?;
+    config.apply_env()?;
+    config.validate()?;
+
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// the loaded config, or the defaults if [`load`] never ran
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("notes-config-{}-{}.toml", name, uuid::Uuid::new_v4()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn vars(vars: &[(&'static str, &str)]) -> impl Fn(&'static str) -> Result<String, env::VarError> {
+        let vars = vars.iter().map(|(var, value)| (*var, value.to_string())).collect::<HashMap<_, _>>();
+        move |var| vars.get(var).cloned().ok_or(env::VarError::NotPresent)
+    }
+
+    #[test]
+    fn settings_are_read_from_the_file() {
+        let path = temp_file("file", "database_path = \"elsewhere.json\"\nsession_lifetime_days = 7\nregistration_open = false\n");
+        let config = Config::from_file(&path, true).unwrap();
+        assert_eq!(config.database_path, PathBuf::from("elsewhere.json"));
+        assert_eq!(config.session_lifetime_days, Some(7));
+        assert!(!config.registration_open);
+        assert_eq!(config.body_char_limit, Config::default().body_char_limit, "left out settings keep their default");
+        fs::remove_file(path).unwrap();
+
+        let missing = env::temp_dir().join(format!("notes-config-missing-{}.toml", uuid::Uuid::new_v4()));
+        assert_eq!(Config::from_file(&missing, false).unwrap().database_path, Config::default().database_path);
+        assert!(matches!(Config::from_file(&missing, true), Err(ConfigError::Read(..))));
+
+        let typo = temp_file("typo", "databse_path = \"notes.json\"\n");
+        assert!(matches!(Config::from_file(&typo, true), Err(ConfigError::Parse(..))));
+        fs::remove_file(typo).unwrap();
+    }
+
+    #[test]
+    fn environment_variables_override_the_file() {
+        let path = temp_file("env", "title_char_limit = 50\nbody_char_limit = 500\n");
+        let mut config = Config::from_file(&path, true).unwrap();
+        fs::remove_file(path).unwrap();
+
+        config.apply_vars(vars(&[("NOTES_TITLE_CHAR_LIMIT", " 80 "), ("NOTES_REGISTRATION_OPEN", "false"), ("NOTES_BIND_ADDRESS", "0.0.0.0:8080")])).unwrap();
+        assert_eq!(config.title_char_limit, 80);
+        assert_eq!(config.body_char_limit, 500, "settings without a variable keep the file's value");
+        assert!(!config.registration_open);
+        assert_eq!(config.bind_address, Some("0.0.0.0:8080".parse().unwrap()));
+
+        assert!(matches!(config.apply_vars(vars(&[("NOTES_BODY_CHAR_LIMIT", "lots")])), Err(ConfigError::Env("NOTES_BODY_CHAR_LIMIT", _))));
+        assert!(matches!(config.apply_vars(vars(&[("NOTES_BIND_ADDRESS", "localhost")])), Err(ConfigError::Env("NOTES_BIND_ADDRESS", _))));
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        assert!(Config::default().validate().is_ok());
+
+        let invalid = |change: fn(&mut Config)| {
+            let mut config = Config::default();
+            change(&mut config);
+            match config.validate() {
+                Err(ConfigError::Invalid(setting, _)) => setting,
+                other => panic!("expected an invalid setting, got {:?}", other),
+            }
+        };
+
+        assert_eq!(invalid(|config| config.database_path = PathBuf::new()), "database_path");
+        assert_eq!(invalid(|config| config.database_path = env::temp_dir()), "database_path");
+        assert_eq!(invalid(|config| config.upload_dir = PathBuf::new()), "upload_dir");
+        assert_eq!(invalid(|config| config.session_lifetime_days = Some(0)), "session_lifetime_days");
+        assert_eq!(invalid(|config| config.title_char_limit = 0), "title_char_limit");
+        assert_eq!(invalid(|config| config.body_char_limit = 0), "body_char_limit");
+        assert_eq!(invalid(|config| config.public_url = "notes.example".to_owned()), "public_url");
+        assert_eq!(invalid(|config| config.log_level = "loud".to_owned()), "log_level");
+    }
+}
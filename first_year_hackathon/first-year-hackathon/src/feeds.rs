@@ -0,0 +1,240 @@
+//! atom and rss feeds of the notes a user has shared publicly, so they can be
+//! followed from a feed reader
+//!
+//! a note is only listed once its owner has put it in their feed, and only while
+//! it has an unexpired share link, entries point at that link so readers never need
+//! an account. having a share link alone doesn't make a note public, those are
+//! meant for the one person they were sent to.
+//!
+//! - `GET /feeds/{username}/atom.xml`
+//! - `GET /feeds/{username}/rss.xml`
+
+use crate::app::render_markdown;
+use crate::model::{NoteKind, SerializedNote, ShareLink, TimeDate};
+use crate::store::{self, NoteStore};
+use actix_web::{error, web, HttpResponse};
+use quick_xml::escape::escape;
+use std::fmt::Write;
+
+/// feed readers only look at the newest entries, anything older just bloats the response
+const MAX_ENTRIES: usize = 50;
+
+struct Entry {
+    note: SerializedNote,
+    link: ShareLink,
+}
+
+impl Entry {
+    fn updated(&self) -> &TimeDate {
+        self.note.last_edit.as_ref().unwrap_or(&self.note.date)
+    }
+
+    fn title(&self) -> &str {
+        if self.note.title.is_empty() {
+            "untitled note"
+        } else {
+            &self.note.title
+        }
+    }
+
+    /// the note as html, checklists as a list of their items since they have no body
+    fn content(&self) -> String {
+        match self.note.kind {
+            NoteKind::Text => render_markdown(&self.note.body),
+            NoteKind::Checklist => {
+                let items = self
+                    .note
+                    .items
+                    .iter()
+                    .map(|item| format!("<li>{} {}</li>", if item.done { "☑" } else { "☐" }, escape(item.text.as_str())))
+                    .collect::<String>();
+                format!("<ul>{}</ul>", items)
+            }
+        }
+    }
+}
+
+/// newest first, only notes in the feed that can currently be opened through their share link
+fn public_entries(username: &str) -> Option<Vec<Entry>> {
+    store::read(|store| entries_in(store, username))
+}
+
+fn entries_in(store: &NoteStore, username: &str) -> Option<Vec<Entry>> {
+    let user = store.user_by_name(username)?;
+
+    let mut entries = store
+        .share_links
+        .values()
+        .filter(|link| !link.is_expired())
+        .filter_map(|link| {
+            let note = store.notes.get(&link.note_id)?;
+            (note.user.uuid == user.uuid && note.in_feed).then(|| Entry {
+                note: note.clone(),
+                link: link.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    //a note can have a fresh link next to an old one, only list it once
+    entries.sort_by(|a, b| b.updated().cmp(a.updated()));
+    let mut seen = std::collections::HashSet::new();
+    entries.retain(|entry| seen.insert(entry.note.id));
+    entries.truncate(MAX_ENTRIES);
+
+    Some(entries)
+}
+
+async fn atom(username: web::Path<String>) -> actix_web::Result<HttpResponse> {
+    let entries = public_entries(&username).ok_or_else(|| error::ErrorNotFound("no such user"))?;
+    let base = crate::config::get().public_url();
+    let updated = entries.first().map_or_else(TimeDate::default, |entry| entry.updated().clone());
+
+    let mut feed = String::new();
+    //writing to a string can't fail
+    let _ = write!(
+        feed,
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>{user}'s notes</title>
+<id>{base}/feeds/{user}/atom.xml</id>
+<link rel="self" href="{base}/feeds/{user}/atom.xml"/>
+<updated>{updated}</updated>
+<author><name>{user}</name></author>
+"#,
+        user = escape(username.as_str()),
+        base = escape(base),
+        updated = updated.to_rfc3339(),
+    );
+
+    for entry in &entries {
+        let _ = write!(
+            feed,
+            r#"<entry>
+<title>{title}</title>
+<id>urn:uuid:{id}</id>
+<link href="{base}{path}"/>
+<published>{published}</published>
+<updated>{updated}</updated>
+<content type="html">{content}</content>
+</entry>
+"#,
+            title = escape(entry.title()),
+            id = entry.note.id,
+            base = escape(base),
+            path = escape(entry.link.path().as_str()),
+            published = entry.note.date.to_rfc3339(),
+            updated = entry.updated().to_rfc3339(),
+            content = escape(entry.content().as_str()),
+        );
+    }
+
+    feed.push_str("</feed>\n");
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/atom+xml; charset=utf-8")
+        .body(feed))
+}
+
+async fn rss(username: web::Path<String>) -> actix_web::Result<HttpResponse> {
+    let entries = public_entries(&username).ok_or_else(|| error::ErrorNotFound("no such user"))?;
+    let base = crate::config::get().public_url();
+
+    let mut feed = String::new();
+    let _ = write!(
+        feed,
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0">
+<channel>
+<title>{user}'s notes</title>
+<link>{base}/feeds/{user}/rss.xml</link>
+<description>notes shared publicly by {user}</description>
+"#,
+        user = escape(username.as_str()),
+        base = escape(base),
+    );
+
+    if let Some(newest) = entries.first() {
+        let _ = writeln!(feed, "<lastBuildDate>{}</lastBuildDate>", newest.updated().to_rfc2822());
+    }
+
+    for entry in &entries {
+        let _ = write!(
+            feed,
+            r#"<item>
+<title>{title}</title>
+<guid isPermaLink="false">urn:uuid:{id}</guid>
+<link>{base}{path}</link>
+<pubDate>{published}</pubDate>
+<description>{content}</description>
+</item>
+"#,
+            title = escape(entry.title()),
+            id = entry.note.id,
+            base = escape(base),
+            path = escape(entry.link.path().as_str()),
+            published = entry.updated().to_rfc2822(),
+            content = escape(entry.content().as_str()),
+        );
+    }
+
+    feed.push_str("</channel>\n</rss>\n");
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/rss+xml; charset=utf-8")
+        .body(feed))
+}
+
+pub fn service() -> actix_web::Scope {
+    web::scope("/feeds")
+        .route("/{username}/atom.xml", web::get().to(atom))
+        .route("/{username}/rss.xml", web::get().to(rss))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{ChecklistItem, SerializedUser};
+
+    #[test]
+    fn only_notes_put_in_the_feed_are_listed() {
+        let user = SerializedUser {
+            username: "ann".to_owned(),
+            creation_date: TimeDate::default(),
+            uuid: uuid::Uuid::new_v4(),
+        };
+
+        let mut store = NoteStore::default();
+        store.users.insert(user.uuid, user.clone());
+        for (title, in_feed) in [("listed", true), ("sent to a friend", false)] {
+            let mut note = SerializedNote::new(title.to_owned(), String::new(), user.clone());
+            note.in_feed = in_feed;
+
+            let link = ShareLink::new(note.id, None);
+            store.share_links.insert(link.token.clone(), link);
+            store.notes.insert(note.id, note);
+        }
+
+        let entries = entries_in(&store, "ann").unwrap();
+        assert_eq!(entries.iter().map(Entry::title).collect::<Vec<_>>(), vec!["listed"]);
+        assert!(entries_in(&store, "nobody").is_none());
+    }
+
+    #[test]
+    fn checklists_list_their_items() {
+        let user = SerializedUser {
+            username: "ann".to_owned(),
+            creation_date: TimeDate::default(),
+            uuid: uuid::Uuid::new_v4(),
+        };
+        let mut note = SerializedNote::new("shopping".to_owned(), String::new(), user);
+        note.kind = NoteKind::Checklist;
+        note.items = vec![ChecklistItem::new("milk".to_owned()), ChecklistItem::new("<eggs>".to_owned())];
+        note.items[0].done = true;
+
+        let entry = Entry {
+            link: ShareLink::new(note.id, None),
+            note,
+        };
+        assert_eq!(entry.content(), "<ul><li>☑ milk</li><li>☐ &lt;eggs&gt;</li></ul>");
+    }
+}
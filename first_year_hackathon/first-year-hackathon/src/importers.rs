@@ -0,0 +1,387 @@
+//! importers for notes exported from other apps, everything is mapped onto
+//! `SerializedNote` so it goes through the same dedupe/preview as our own backups
+
+use crate::app::User;
+use crate::model::{ChecklistItem, NoteKind, SerializedNote, SerializedUser, TimeDate};
+use quick_xml::{events::Event, Reader};
+use serde::Deserialize;
+use std::io::{Cursor, Read};
+use uuid::Uuid;
+
+/// neither keep nor evernote give us a stable id we can reuse, so derive one from
+/// the content that identifies a note. importing the same archive twice then
+/// dedupes instead of creating copies. the creation time goes in as epoch seconds
+/// so the id doesn't change with the importer's time zone
+fn stable_id(source: &str, title: &str, created: &TimeDate) -> Uuid {
+    let key = format!("{}:{}:{}", source, created.epoch(), title);
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, key.as_bytes())
+}
+
+fn new_note(id: Uuid, title: String, owner: &User, created: TimeDate, last_edit: Option<TimeDate>) -> SerializedNote {
+    SerializedNote {
+        id,
+        date: created,
+        last_edit,
+        ..SerializedNote::new(title, String::new(), SerializedUser::from(owner))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepLabel {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepListItem {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    is_checked: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepNote {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text_content: String,
+    #[serde(default)]
+    color: String,
+    #[serde(default)]
+    is_pinned: bool,
+    #[serde(default)]
+    is_trashed: bool,
+    #[serde(default)]
+    created_timestamp_usec: i64,
+    #[serde(default)]
+    user_edited_timestamp_usec: i64,
+    #[serde(default)]
+    labels: Vec<KeepLabel>,
+    list_content: Option<Vec<KeepListItem>>,
+}
+
+/// keep's named colours, lightened to match the pastel palette we generate
+fn keep_colour(colour: &str) -> String {
+    let (r, g, b) = match colour {
+        "RED" => (246, 173, 170),
+        "ORANGE" => (251, 206, 156),
+        "YELLOW" => (255, 244, 170),
+        "GREEN" => (214, 244, 192),
+        "TEAL" => (202, 240, 232),
+        "BLUE" => (212, 233, 245),
+        "CERULEAN" | "DARK_BLUE" => (200, 214, 240),
+        "PURPLE" => (226, 212, 245),
+        "PINK" => (250, 214, 232),
+        "BROWN" => (235, 218, 200),
+        "GRAY" => (232, 234, 237),
+        _ => return crate::model::get_new_pastel_colour(),
+    };
+
+    format!("rgb({}, {}, {})", r, g, b)
+}
+
+fn from_keep(keep: KeepNote, owner: &User) -> SerializedNote {
+    let created = TimeDate::from_epoch(keep.created_timestamp_usec / 1_000_000);
+    let edited = (keep.user_edited_timestamp_usec > 0).then(|| TimeDate::from_epoch(keep.user_edited_timestamp_usec / 1_000_000));
+
+    let mut note = new_note(stable_id("keep", &keep.title, &created), keep.title, owner, created, edited);
+
+    match keep.list_content {
+        Some(items) => {
+            note.kind = NoteKind::Checklist;
+            note.items = items
+                .into_iter()
+                .map(|item| ChecklistItem {
+                    id: Uuid::new_v4(),
+                    text: item.text,
+                    done: item.is_checked,
+                })
+                .collect();
+        }
+        None => note.body = keep.text_content,
+    }
+
+    note.colour = keep_colour(&keep.color);
+    note.pinned = keep.is_pinned;
+    note.tags = keep.labels.into_iter().map(|label| label.name).collect();
+    note
+}
+
+/// accepts either a single note's json or the whole takeout zip, trashed notes are left behind
+pub fn parse_keep(bytes: &[u8], owner: &User) -> Result<Vec<SerializedNote>, String> {
+    let keep_notes = if bytes.starts_with(b"PK") {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("not a takeout archive: {}", e))?;
+        let mut keep_notes = Vec::new();
+
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).map_err(|e| e.to_string())?;
+            if !file.is_file() || !file.name().contains("Keep/") || !file.name().ends_with(".json") {
+                continue;
+            }
+
+            let name = file.name().to_owned();
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).map_err(|e| format!("{}: {}", name, e))?;
+            keep_notes.push(serde_json::from_str::<KeepNote>(&contents).map_err(|e| format!("{}: {}", name, e))?);
+        }
+
+        keep_notes
+    } else {
+        vec![serde_json::from_slice::<KeepNote>(bytes).map_err(|e| format!("not a keep note: {}", e))?]
+    };
+
+    Ok(keep_notes
+        .into_iter()
+        .filter(|keep| !keep.is_trashed)
+        .map(|keep| from_keep(keep, owner))
+        .collect())
+}
+
+/// enex dates look like `20230820T101500Z`
+fn parse_enex_date(value: &str) -> Option<TimeDate> {
+    let naive = chrono::NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ").ok()?;
+    Some(TimeDate::from_epoch(chrono::TimeZone::from_utc_datetime(&chrono::Utc, &naive).timestamp()))
+}
+
+/// flattens enml (evernote's xhtml dialect) into text, turning `<en-todo>` into
+/// task list markers so checklists survive the trip
+fn enml_to_text(enml: &str) -> String {
+    const BLOCKS: &[&[u8]] = &[b"div", b"p", b"br", b"li", b"tr", b"h1", b"h2", b"h3", b"h4", b"h5", b"h6"];
+
+    fn push_newline(text: &mut String) {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+
+    let mut reader = Reader::from_str(enml);
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(tag)) | Ok(Event::Empty(tag)) if tag.name().as_ref() == b"en-todo" => {
+                let checked = tag
+                    .try_get_attribute("checked")
+                    .ok()
+                    .flatten()
+                    .is_some_and(|attr| attr.value.as_ref() == b"true");
+
+                push_newline(&mut text);
+                text.push_str(if checked { "- [x] " } else { "- [ ] " });
+            }
+            Ok(Event::Start(tag)) | Ok(Event::Empty(tag)) if BLOCKS.contains(&tag.name().as_ref()) => push_newline(&mut text),
+            Ok(Event::End(tag)) if BLOCKS.contains(&tag.name().as_ref()) => push_newline(&mut text),
+            Ok(Event::Text(content)) => match content.unescape() {
+                Ok(content) => text.push_str(&content),
+                //enml leans on html entities like &nbsp; that xml doesn't know about
+                Err(_) => text.push_str(&String::from_utf8_lossy(&content).replace("&nbsp;", " ")),
+            },
+            Ok(Event::CData(content)) => text.push_str(&String::from_utf8_lossy(&content)),
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    text.trim().to_owned()
+}
+
+#[derive(Default)]
+struct EnexNote {
+    title: String,
+    content: String,
+    created: Option<TimeDate>,
+    updated: Option<TimeDate>,
+    tags: Vec<String>,
+}
+
+fn from_enex(enex: EnexNote, owner: &User) -> SerializedNote {
+    let created = enex.created.unwrap_or_default();
+    let mut note = new_note(stable_id("enex", &enex.title, &created), enex.title, owner, created, enex.updated);
+
+    let body = enml_to_text(&enex.content);
+    let items = crate::markdown_files::parse_checklist(&body);
+
+    //only notes made up entirely of todos become checklists, anything else keeps its text
+    if !items.is_empty() && items.len() == body.lines().filter(|line| !line.trim().is_empty()).count() {
+        note.kind = NoteKind::Checklist;
+        note.items = items;
+    } else {
+        note.body = body;
+    }
+
+    note.tags = enex.tags;
+    note
+}
+
+pub fn parse_enex(contents: &str, owner: &User) -> Result<Vec<SerializedNote>, String> {
+    let mut reader = Reader::from_str(contents);
+    reader.trim_text(true);
+
+    let mut notes = Vec::new();
+    let mut current = None::<EnexNote>;
+    let mut field = Vec::<u8>::new();
+    let mut saw_export = false;
+
+    loop {
+        match reader.read_event().map_err(|e| format!("invalid enex at byte {}: {}", reader.buffer_position(), e))? {
+            Event::Start(tag) => match tag.name().as_ref() {
+                b"en-export" => saw_export = true,
+                b"note" => current = Some(EnexNote::default()),
+                name => field = name.to_vec(),
+            },
+            Event::End(tag) => match tag.name().as_ref() {
+                b"note" => {
+                    if let Some(enex) = current.take() {
+                        notes.push(from_enex(enex, owner));
+                    }
+                }
+                _ => field.clear(),
+            },
+            Event::Text(text) => {
+                let text = text.unescape().map_err(|e| e.to_string())?.into_owned();
+                apply_enex_field(current.as_mut(), &field, text);
+            }
+            Event::CData(text) => {
+                let text = String::from_utf8_lossy(&text).into_owned();
+                apply_enex_field(current.as_mut(), &field, text);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !saw_export {
+        return Err("not an evernote export, missing <en-export>".to_owned());
+    }
+
+    Ok(notes)
+}
+
+fn apply_enex_field(note: Option<&mut EnexNote>, field: &[u8], text: String) {
+    let Some(note) = note else {
+        return;
+    };
+
+    match field {
+        b"title" => note.title = text,
+        b"content" => note.content.push_str(&text),
+        b"created" => note.created = parse_enex_date(&text),
+        b"updated" => note.updated = parse_enex_date(&text),
+        b"tag" => note.tags.push(text),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner() -> User {
+        User::new_user("someone".to_owned())
+    }
+
+    const KEEP_NOTE: &str = r#"{
+        "title": "groceries",
+        "textContent": "milk and eggs",
+        "color": "YELLOW",
+        "isPinned": true,
+        "createdTimestampUsec": 1700000000000000,
+        "userEditedTimestampUsec": 1700000600000000,
+        "labels": [{"name": "home"}]
+    }"#;
+
+    #[test]
+    fn keep_notes_are_mapped() {
+        let notes = parse_keep(KEEP_NOTE.as_bytes(), &owner()).unwrap();
+
+        assert_eq!(notes.len(), 1);
+        let note = &notes[0];
+        assert_eq!(note.title, "groceries");
+        assert_eq!(note.body, "milk and eggs");
+        assert_eq!(note.colour, "rgb(255, 244, 170)");
+        assert!(note.pinned);
+        assert_eq!(note.date, TimeDate::from_epoch(1_700_000_000));
+        assert_eq!(note.last_edit, Some(TimeDate::from_epoch(1_700_000_600)));
+        assert_eq!(note.tags, vec!["home".to_owned()]);
+    }
+
+    #[test]
+    fn keep_lists_become_checklists() {
+        let json = r#"{"title": "todo", "listContent": [{"text": "a", "isChecked": true}, {"text": "b"}, {}]}"#;
+        let note = parse_keep(json.as_bytes(), &owner()).unwrap().remove(0);
+
+        assert_eq!(note.kind, NoteKind::Checklist);
+        assert_eq!(
+            note.items.iter().map(|item| (item.text.as_str(), item.done)).collect::<Vec<_>>(),
+            vec![("a", true), ("b", false), ("", false)]
+        );
+    }
+
+    #[test]
+    fn trashed_keep_notes_are_left_behind() {
+        let json = r#"{"title": "gone", "isTrashed": true}"#;
+        assert!(parse_keep(json.as_bytes(), &owner()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn importing_twice_gives_the_same_ids() {
+        let first = parse_keep(KEEP_NOTE.as_bytes(), &owner()).unwrap();
+        let second = parse_keep(KEEP_NOTE.as_bytes(), &owner()).unwrap();
+
+        assert_eq!(first[0].id, second[0].id);
+        assert_eq!(first[0].id, stable_id("keep", "groceries", &TimeDate::from_epoch(1_700_000_000)));
+    }
+
+    const ENEX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<en-export>
+  <note>
+    <title>trip</title>
+    <content><![CDATA[<en-note><div>pack &amp; go</div><div>early</div></en-note>]]></content>
+    <created>20230820T101500Z</created>
+    <updated>20230821T080000Z</updated>
+    <tag>travel</tag>
+  </note>
+  <note>
+    <title>chores</title>
+    <content><![CDATA[<en-note><div><en-todo checked="true"/>dishes</div><div><en-todo/>laundry</div></en-note>]]></content>
+    <created>20230820T101500Z</created>
+  </note>
+</en-export>"#;
+
+    #[test]
+    fn enex_notes_are_mapped() {
+        let notes = parse_enex(ENEX, &owner()).unwrap();
+
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].title, "trip");
+        assert_eq!(notes[0].body, "pack & go\nearly");
+        assert_eq!(notes[0].tags, vec!["travel".to_owned()]);
+        assert_eq!(notes[0].date, parse_enex_date("20230820T101500Z").unwrap());
+        assert_eq!(notes[0].last_edit, parse_enex_date("20230821T080000Z"));
+    }
+
+    #[test]
+    fn enex_todos_become_checklists() {
+        let note = parse_enex(ENEX, &owner()).unwrap().remove(1);
+
+        assert_eq!(note.kind, NoteKind::Checklist);
+        assert_eq!(
+            note.items.iter().map(|item| (item.text.as_str(), item.done)).collect::<Vec<_>>(),
+            vec![("dishes", true), ("laundry", false)]
+        );
+    }
+
+    #[test]
+    fn enex_needs_an_export_element() {
+        assert!(parse_enex("<notes></notes>", &owner()).is_err());
+    }
+
+    #[test]
+    fn enex_dates_are_utc() {
+        assert_eq!(parse_enex_date("19700101T000100Z"), Some(TimeDate::from_epoch(60)));
+        assert_eq!(parse_enex_date("yesterday"), None);
+    }
+}
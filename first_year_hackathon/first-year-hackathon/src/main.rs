@@ -7,8 +7,45 @@ async fn main() -> std::io::Result<()> {
     use leptos_actix::{generate_route_list, LeptosRoutes};
     use leptos_start::app::*;
 
-    let conf = get_configuration(None).await.unwrap();
+    let settings = match leptos_start::config::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut conf = match get_configuration(None).await {
+        Ok(conf) => conf,
+        Err(e) => {
+            eprintln!("couldn't read the leptos configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(addr) = settings.bind_address {
+        conf.leptos_options.site_addr = addr;
+    }
     let addr = conf.leptos_options.site_addr;
+
+    //validate() already checked the level parses
+    if let Some(level) = settings.log_level() {
+        simple_logger::init_with_level(level).map_err(std::io::Error::other)?;
+    }
+
+    leptos_start::store::init(&settings.database_path)?;
+    leptos_start::attachments::init(&settings.upload_dir)?;
+    // clear out attachments whose note was never saved, once at startup and then every so often
+    rt::spawn(async {
+        let mut sweep = rt::time::interval(leptos_start::attachments::SWEEP_INTERVAL);
+        loop {
+            sweep.tick().await;
+            match rt::task::spawn_blocking(leptos_start::attachments::remove_orphans).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!("failed to sweep attachments: {}", e),
+                Err(e) => warn!("attachment sweep panicked: {}", e),
+            }
+        }
+    });
     // Generate the list of routes in your Leptos App
     let routes = generate_route_list(|cx| view! { cx, <App/> });
 
@@ -18,6 +55,14 @@ async fn main() -> std::io::Result<()> {
 
         App::new()
             .route("/api/{tail:.*}", leptos_actix::handle_server_fns())
+            // note attachments, uploaded and served by content hash
+            .service(leptos_start::attachments::service())
+            // token authenticated json api
+            .service(leptos_start::api::service())
+            // atom/rss feeds of publicly shared notes
+            .service(leptos_start::feeds::service())
+            // icalendar subscription of due dates and reminders
+            .service(leptos_start::calendar::service())
             // serve JS/WASM/CSS from `pkg`
             .service(Files::new("/pkg", format!("{site_root}/pkg")))
             // serve other assets from the `assets` directory
@@ -0,0 +1,278 @@
+//! notes as a folder of markdown files, each with yaml front matter holding
+//! everything that isn't the body. lets people keep notes in git and edit them
+//! with whatever tools they like
+
+use crate::app::User;
+use crate::model::{get_new_pastel_colour, ChecklistItem, NoteKind, SerializedNote, SerializedUser, TimeDate};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read, Write};
+use uuid::Uuid;
+
+const FRONT_MATTER_FENCE: &str = "---";
+
+#[derive(Serialize, Deserialize)]
+struct FrontMatter {
+    id: Uuid,
+    title: String,
+    author: String,
+    created: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_edit: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    colour: Option<String>,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    checklist: bool,
+}
+
+/// `My Note!` -> `my-note`
+fn slugify(title: &str) -> String {
+    let slug = title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if slug.is_empty() {
+        "untitled".to_owned()
+    } else {
+        slug.chars().take(60).collect()
+    }
+}
+
+/// the id suffix keeps two notes with the same title from clobbering each other
+fn file_name(note: &SerializedNote) -> String {
+    let id = note.id.simple().to_string();
+    format!("{}-{}.md", slugify(&note.title), &id[..8])
+}
+
+pub fn to_markdown(note: &SerializedNote) -> Result<String, String> {
+    let checklist = note.kind == NoteKind::Checklist;
+
+    let front_matter = FrontMatter {
+        id: note.id,
+        title: note.title.clone(),
+        author: note.user.username.clone(),
+        created: note.date.to_rfc3339(),
+        last_edit: note.last_edit.as_ref().map(TimeDate::to_rfc3339),
+        tags: note.tags.clone(),
+        colour: Some(note.colour.clone()),
+        pinned: note.pinned,
+        checklist,
+    };
+
+    let yaml = serde_yaml::to_string(&front_matter).map_err(|e| e.to_string())?;
+
+    //checklists are written as task lists so they read naturally in other editors
+    let body = if checklist {
+        note.items
+            .iter()
+            .map(|item| format!("- [{}] {}\n", if item.done { "x" } else { " " }, item.text))
+            .collect::<String>()
+    } else {
+        note.body.clone()
+    };
+
+    Ok(format!("{fence}\n{yaml}{fence}\n\n{body}", fence = FRONT_MATTER_FENCE))
+}
+
+pub(crate) fn parse_checklist(body: &str) -> Vec<ChecklistItem> {
+    body.lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            let (done, text) = if let Some(text) = line.strip_prefix("- [ ] ") {
+                (false, text)
+            } else if let Some(text) = line.strip_prefix("- [x] ").or_else(|| line.strip_prefix("- [X] ")) {
+                (true, text)
+            } else {
+                return None;
+            };
+
+            Some(ChecklistItem {
+                id: Uuid::new_v4(),
+                text: text.trim().to_owned(),
+                done,
+            })
+        })
+        .collect()
+}
+
+/// parses a single markdown file, the author in the front matter is informational
+/// only, notes always belong to whoever imports them
+pub fn from_markdown(contents: &str, owner: &User) -> Result<SerializedNote, String> {
+    let contents = contents.trim_start_matches('\u{feff}');
+
+    let rest = contents
+        .strip_prefix(FRONT_MATTER_FENCE)
+        .and_then(|rest| rest.strip_prefix('\n').or_else(|| rest.strip_prefix("\r\n")))
+        .ok_or("missing front matter")?;
+
+    let end = rest
+        .find(&format!("\n{}", FRONT_MATTER_FENCE))
+        .ok_or("front matter is never closed")?;
+
+    let front_matter = serde_yaml::from_str::<FrontMatter>(&rest[..end]).map_err(|e| format!("bad front matter: {}", e))?;
+
+    let body = rest[end + 1 + FRONT_MATTER_FENCE.len()..]
+        .trim_start_matches(['\r', '\n'])
+        .to_owned();
+
+    let created = TimeDate::from_rfc3339(&front_matter.created).ok_or("created is not an rfc3339 date")?;
+    let last_edit = match &front_matter.last_edit {
+        Some(last_edit) => Some(TimeDate::from_rfc3339(last_edit).ok_or("last_edit is not an rfc3339 date")?),
+        None => None,
+    };
+
+    let (kind, items, body) = if front_matter.checklist {
+        (NoteKind::Checklist, parse_checklist(&body), String::new())
+    } else {
+        (NoteKind::Text, Vec::new(), body)
+    };
+
+    Ok(SerializedNote {
+        id: front_matter.id,
+        title: front_matter.title,
+        body,
+        user: SerializedUser::from(owner),
+        date: created,
+        last_edit,
+        access: Vec::new(),
+        share_link: None,
+        comments: Vec::new(),
+        kind,
+        items,
+        attachments: Vec::new(),
+        due: None,
+        reminder: None,
+        reminder_sent: false,
+        recurrence: None,
+        links: Vec::new(),
+        tags: front_matter.tags,
+        colour: front_matter.colour.unwrap_or_else(get_new_pastel_colour),
+        pinned: front_matter.pinned,
+        in_feed: false,
+    })
+}
+
+pub fn write_zip(notes: &[SerializedNote]) -> Result<Vec<u8>, String> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    for note in notes {
+        zip.start_file(file_name(note), options).map_err(|e| e.to_string())?;
+        zip.write_all(to_markdown(note)?.as_bytes()).map_err(|e| e.to_string())?;
+    }
+
+    let cursor = zip.finish().map_err(|e| e.to_string())?;
+    Ok(cursor.into_inner())
+}
+
+/// reads every `.md` file in the archive, any file that fails to parse fails the whole import
+/// so nobody ends up with half a backup restored
+pub fn read_zip(bytes: &[u8], owner: &User) -> Result<Vec<SerializedNote>, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("not a zip file: {}", e))?;
+    let mut notes = Vec::new();
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(|e| e.to_string())?;
+        if !file.is_file() || !file.name().ends_with(".md") {
+            continue;
+        }
+
+        let name = file.name().to_owned();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).map_err(|e| format!("{}: {}", name, e))?;
+
+        notes.push(from_markdown(&contents, owner).map_err(|e| format!("{}: {}", name, e))?);
+    }
+
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner() -> User {
+        User::new_user("someone".to_owned())
+    }
+
+    fn note(title: &str, body: &str) -> SerializedNote {
+        SerializedNote {
+            last_edit: Some(TimeDate::from_epoch(1_700_000_000)),
+            tags: vec!["work".to_owned(), "ideas".to_owned()],
+            pinned: true,
+            ..SerializedNote::new(title.to_owned(), body.to_owned(), SerializedUser::from(&owner()))
+        }
+    }
+
+    #[test]
+    fn text_notes_round_trip() {
+        let original = note("My Note!", "some *markdown*\n\nover two paragraphs\n");
+        let parsed = from_markdown(&to_markdown(&original).unwrap(), &owner()).unwrap();
+
+        assert_eq!(parsed.id, original.id);
+        assert_eq!(parsed.title, original.title);
+        assert_eq!(parsed.body, original.body);
+        assert_eq!(parsed.date, original.date);
+        assert_eq!(parsed.last_edit, original.last_edit);
+        assert_eq!(parsed.tags, original.tags);
+        assert_eq!(parsed.colour, original.colour);
+        assert!(parsed.pinned);
+        assert_eq!(parsed.kind, NoteKind::Text);
+    }
+
+    #[test]
+    fn checklists_round_trip_as_task_lists() {
+        let mut original = note("shopping", "");
+        original.kind = NoteKind::Checklist;
+        original.items = vec![
+            ChecklistItem { id: Uuid::new_v4(), text: "milk".to_owned(), done: true },
+            ChecklistItem { id: Uuid::new_v4(), text: "eggs".to_owned(), done: false },
+        ];
+
+        let markdown = to_markdown(&original).unwrap();
+        assert!(markdown.ends_with("- [x] milk\n- [ ] eggs\n"));
+
+        let parsed = from_markdown(&markdown, &owner()).unwrap();
+        assert_eq!(parsed.kind, NoteKind::Checklist);
+        assert_eq!(
+            parsed.items.iter().map(|item| (item.text.as_str(), item.done)).collect::<Vec<_>>(),
+            vec![("milk", true), ("eggs", false)]
+        );
+    }
+
+    #[test]
+    fn imported_notes_belong_to_the_importer() {
+        let importer = owner();
+        let parsed = from_markdown(&to_markdown(&note("title", "body")).unwrap(), &importer).unwrap();
+
+        assert_eq!(parsed.user, SerializedUser::from(&importer));
+    }
+
+    #[test]
+    fn front_matter_is_required() {
+        assert!(from_markdown("just a body", &owner()).is_err());
+        assert!(from_markdown("---\ntitle: never closed\n", &owner()).is_err());
+    }
+
+    #[test]
+    fn zips_round_trip_notes_with_the_same_title() {
+        let notes = vec![note("same", "first"), note("same", "second")];
+        let parsed = read_zip(&write_zip(&notes).unwrap(), &owner()).unwrap();
+
+        let mut bodies = parsed.iter().map(|note| note.body.as_str()).collect::<Vec<_>>();
+        bodies.sort();
+        assert_eq!(bodies, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn file_names_are_slugs() {
+        assert_eq!(slugify("My Note!"), "my-note");
+        assert_eq!(slugify("?!"), "untitled");
+    }
+}
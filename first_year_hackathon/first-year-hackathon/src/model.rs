@@ -0,0 +1,824 @@
+//! the plain data the web app, the server and the cli pass between each other,
+//! along with the rules that go with it. nothing in here touches the ui
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+use uuid::Uuid;
+
+pub const MAX_ATTACHMENT_BYTES: u64 = 10 * 1024 * 1024;
+
+/// content types we accept uploads for, along with the extension the blob is stored under
+pub const ALLOWED_ATTACHMENT_TYPES: &[(&str, &str)] = &[
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+    ("application/pdf", "pdf"),
+    ("text/plain", "txt"),
+    ("application/zip", "zip"),
+];
+
+/// a `[[label]]` in a note body, pinned to the id of the note it pointed at when
+/// it was written so renaming the target doesn't break the link
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct NoteLink {
+    pub label: String,
+    pub target: Uuid,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub name: String,
+    pub content_type: String,
+    pub size: u64,
+    /// name of the content addressed blob on the server
+    pub blob: String,
+}
+
+impl Attachment {
+    pub fn url(&self) -> String {
+        format!("/attachments/{}", self.blob)
+    }
+
+    /// a small png of an image attachment, made the first time it's asked for
+    pub fn thumbnail_url(&self) -> String {
+        format!("/attachments/{}/thumbnail", self.blob)
+    }
+
+    pub(crate) fn is_image(&self) -> bool {
+        self.content_type.starts_with("image/")
+    }
+
+    pub(crate) fn format_size(&self) -> String {
+        match self.size {
+            size if size < 1024 => format!("{} B", size),
+            size if size < 1024 * 1024 => format!("{:.1} KB", size as f64 / 1024.0),
+            size => format!("{:.1} MB", size as f64 / (1024.0 * 1024.0)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum NoteKind {
+    #[default]
+    Text,
+    Checklist,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ChecklistItem {
+    pub id: Uuid,
+    pub text: String,
+    pub done: bool,
+}
+
+impl ChecklistItem {
+    pub(crate) fn new(text: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            text,
+            done: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: Uuid,
+    pub author: SerializedUser,
+    pub body: String,
+    pub date: TimeDate,
+    pub last_edit: Option<TimeDate>,
+}
+
+impl Comment {
+    #[cfg(feature = "ssr")]
+    pub(crate) fn new(author: SerializedUser, body: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            author,
+            body,
+            date: TimeDate::default(),
+            last_edit: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize)]
+pub enum AccessLevel {
+    Read,
+    Write,
+}
+
+impl AccessLevel {
+    pub(crate) fn label(&self) -> &'static str {
+        match *self {
+            Self::Read => "read",
+            Self::Write => "write",
+        }
+    }
+
+    pub(crate) fn from_label(label: &str) -> Self {
+        match label {
+            "write" => Self::Write,
+            _ => Self::Read,
+        }
+    }
+}
+
+/// a single entry in a note's access control list
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct NoteAccess {
+    pub(crate) user: SerializedUser,
+    pub(crate) level: AccessLevel,
+}
+
+/// an unguessable token that lets anyone read a single note without logging in
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub token: String,
+    pub note_id: Uuid,
+    pub expires: Option<TimeDate>,
+}
+
+impl ShareLink {
+    #[cfg(feature = "ssr")]
+    pub(crate) fn new(note_id: Uuid, expires: Option<TimeDate>) -> Self {
+        //two v4 uuids give us ~244 bits from the os rng
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+        Self {
+            token,
+            note_id,
+            expires,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires.as_ref().is_some_and(TimeDate::has_passed)
+    }
+
+    pub fn path(&self) -> String {
+        format!("/s/{}", self.token)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SerializedUser {
+    pub username: String,
+    pub creation_date: TimeDate,
+    pub uuid: Uuid,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize)]
+pub struct TimeDate {
+    pub(crate) epoch_time: i64,
+}
+
+impl TimeDate {
+    const SECONDS_IN_DAY: i64 = 24 * 60 * 60;
+    const SECONDS_IN_WEEK: i64 = 7 * Self::SECONDS_IN_DAY;
+
+    pub fn from_epoch(epoch_time: i64) -> Self {
+        Self {
+            epoch_time,
+        }
+    }
+
+    pub fn epoch(&self) -> i64 {
+        self.epoch_time
+    }
+
+    pub fn days_from_now(days: i64) -> Self {
+        Self {
+            epoch_time: chrono::offset::Utc::now().timestamp() + days * Self::SECONDS_IN_DAY,
+        }
+    }
+
+    pub fn has_passed(&self) -> bool {
+        self.epoch_time <= chrono::offset::Utc::now().timestamp()
+    }
+
+    /// parses the value of a `datetime-local` input, which is in the viewer's local time
+    pub fn from_local_input(value: &str) -> Option<Self> {
+        let naive = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").ok()?;
+        let local = chrono::Local.from_local_datetime(&naive).earliest()?;
+
+        Some(Self {
+            epoch_time: local.timestamp(),
+        })
+    }
+
+    pub fn to_local_input(&self) -> String {
+        self.to_local().format("%Y-%m-%dT%H:%M").to_string()
+    }
+
+    pub fn to_rfc3339(&self) -> String {
+        self.to_local().to_rfc3339()
+    }
+
+    /// the date format rss uses
+    pub fn to_rfc2822(&self) -> String {
+        self.to_local().to_rfc2822()
+    }
+
+    /// a utc icalendar DATE-TIME, e.g. `20240131T090000Z`
+    pub fn to_ical(&self) -> String {
+        chrono::Utc.timestamp_opt(self.epoch_time, 0).unwrap().format("%Y%m%dT%H%M%SZ").to_string()
+    }
+
+    /// the wall clock time in `tz`
+    pub fn to_naive_in<Tz: chrono::TimeZone>(&self, tz: &Tz) -> chrono::NaiveDateTime {
+        tz.timestamp_opt(self.epoch_time, 0).unwrap().naive_local()
+    }
+
+    pub fn from_rfc3339(value: &str) -> Option<Self> {
+        let datetime = chrono::DateTime::parse_from_rfc3339(value).ok()?;
+
+        Some(Self {
+            epoch_time: datetime.timestamp(),
+        })
+    }
+
+    pub(crate) fn to_local(&self) -> chrono::DateTime<chrono::Local> {
+        chrono::Local.timestamp_opt(self.epoch_time, 0).unwrap()
+    }
+
+    /// wall clock times that fall in a dst gap don't exist, so they get pushed forward an hour
+    pub(crate) fn from_naive_in<Tz: chrono::TimeZone>(tz: &Tz, naive: chrono::NaiveDateTime) -> Self {
+        let local = tz.from_local_datetime(&naive).earliest()
+            .or_else(|| tz.from_local_datetime(&(naive + chrono::Duration::hours(1))).earliest())
+            .unwrap();
+
+        Self {
+            epoch_time: local.timestamp(),
+        }
+    }
+
+    pub(crate) fn offset_by(&self, seconds: i64) -> Self {
+        Self {
+            epoch_time: self.epoch_time + seconds,
+        }
+    }
+
+    pub fn format_date(&self) -> String {
+        let local_date = chrono::Local.timestamp_opt(self.epoch_time, 0).unwrap().date_naive();
+        local_date.to_string()
+    }
+
+    pub fn format_time(&self) -> String {
+        let current_time = chrono::offset::Utc::now().timestamp();
+
+        let comp_time = self.epoch_time;
+
+        if current_time - comp_time > Self::SECONDS_IN_WEEK {
+            return String::new()
+        }
+
+        let time = chrono::Local.timestamp_opt(comp_time, 0).unwrap();
+        let minutes = time.minute();
+
+        let minutes = if minutes < 10 {
+            format!("0{}", minutes)
+        }else{
+            format!("{}", minutes)
+        };
+
+        format!{"{}:{}", time.hour(), minutes}
+    }
+
+    pub fn format_datetime(&self) -> String {
+        let maybe_time = self.format_time();
+        let date = self.format_date();
+
+
+        if maybe_time.is_empty() {
+            date
+        }else{
+            format!{"{} @ {}", date, maybe_time}
+        }
+    }
+
+}
+
+impl Default for TimeDate {
+    fn default() -> Self {
+        Self {
+            epoch_time: chrono::offset::Utc::now().timestamp(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum RecurrenceUnit {
+    Days,
+    Weeks,
+    Months,
+}
+
+/// how a note's due date repeats, all arithmetic happens on local wall clock time
+/// so a 9am deadline stays at 9am either side of a dst change
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum Recurrence {
+    Daily,
+    Weekdays,
+    /// days counted from monday, so 0 is monday and 6 is sunday
+    Weekly(Vec<u32>),
+    MonthlyOnDay(u32),
+    /// `day` is the day of the month that monthly repeats aim for, so a due date
+    /// clamped to the end of a short month springs back to the 31st afterwards
+    Every {
+        interval: u32,
+        unit: RecurrenceUnit,
+        #[serde(default)]
+        day: Option<u32>,
+    },
+}
+
+pub(crate) const WEEKDAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+fn add_months(date: chrono::NaiveDate, months: u32, day: u32) -> chrono::NaiveDate {
+    let total = date.year() * 12 + date.month0() as i32 + months as i32;
+    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+
+    //clamp to the end of shorter months, e.g. the 31st becomes the 30th in april
+    (1..=day.clamp(1, 31)).rev()
+        .find_map(|day| chrono::NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap()
+}
+
+impl Recurrence {
+    pub(crate) fn next_date(&self, date: chrono::NaiveDate) -> chrono::NaiveDate {
+        match self {
+            Self::Daily => date + chrono::Duration::days(1),
+            Self::Weekdays => (1..=3)
+                .map(|days| date + chrono::Duration::days(days))
+                .find(|next| next.weekday().num_days_from_monday() < 5)
+                .unwrap(),
+            //days past sunday can't match anything, so they count as no days at all
+            Self::Weekly(days) if !days.iter().any(|day| *day < 7) => date + chrono::Duration::weeks(1),
+            Self::Weekly(days) => (1..=7)
+                .map(|offset| date + chrono::Duration::days(offset))
+                .find(|next| days.contains(&next.weekday().num_days_from_monday()))
+                .unwrap(),
+            Self::MonthlyOnDay(day) => {
+                let this_month = add_months(date, 0, *day);
+                if this_month > date { this_month } else { add_months(date, 1, *day) }
+            }
+            Self::Every { interval, unit, day } => {
+                let interval = (*interval).max(1);
+                match unit {
+                    RecurrenceUnit::Days => date + chrono::Duration::days(interval as i64),
+                    RecurrenceUnit::Weeks => date + chrono::Duration::weeks(interval as i64),
+                    RecurrenceUnit::Months => add_months(date, interval, day.unwrap_or(date.day())),
+                }
+            }
+        }
+    }
+
+    /// the first occurrence after `due` that is also in the future
+    pub fn next_after(&self, due: &TimeDate) -> TimeDate {
+        self.next_after_in(&chrono::Local, due, &TimeDate::default())
+    }
+
+    pub(crate) fn next_after_in<Tz: chrono::TimeZone>(&self, tz: &Tz, due: &TimeDate, now: &TimeDate) -> TimeDate {
+        let local = due.to_naive_in(tz);
+        let (mut date, time) = (local.date(), local.time());
+
+        loop {
+            date = self.next_date(date);
+            let next = TimeDate::from_naive_in(tz, date.and_time(time));
+            if next > *now {
+                return next;
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Daily => "daily".to_owned(),
+            Self::Weekdays => "on weekdays".to_owned(),
+            Self::Weekly(days) => {
+                let names = days.iter().filter_map(|day| WEEKDAY_NAMES.get(*day as usize).copied()).collect::<Vec<_>>();
+                format!("weekly on {}", names.join(", "))
+            }
+            Self::MonthlyOnDay(day) => format!("monthly on day {}", day),
+            Self::Every { interval, unit, .. } => {
+                let unit = match unit {
+                    RecurrenceUnit::Days => "days",
+                    RecurrenceUnit::Weeks => "weeks",
+                    RecurrenceUnit::Months => "months",
+                };
+                format!("every {} {}", interval, unit)
+            }
+        }
+    }
+
+    /// the icalendar RRULE for this recurrence, starting on `start`
+    pub fn to_rrule(&self, start: chrono::NaiveDate) -> String {
+        //rrule skips months that are too short instead of clamping, so pick the last
+        //day up to `day` that the month actually has
+        fn last_day_up_to(day: u32) -> String {
+            let days = (28..=day.min(31)).map(|day| day.to_string()).collect::<Vec<_>>();
+            format!("BYMONTHDAY={};BYSETPOS=-1", days.join(","))
+        }
+
+        const BYDAY: [&str; 7] = ["MO", "TU", "WE", "TH", "FR", "SA", "SU"];
+
+        match self {
+            Self::Daily => "FREQ=DAILY".to_owned(),
+            Self::Weekdays => "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR".to_owned(),
+            Self::Weekly(days) => {
+                //next_date ignores days past sunday, and so does the calendar
+                let days = days.iter().filter_map(|day| BYDAY.get(*day as usize).copied()).collect::<Vec<_>>();
+                if days.is_empty() {
+                    "FREQ=WEEKLY".to_owned()
+                } else {
+                    format!("FREQ=WEEKLY;BYDAY={}", days.join(","))
+                }
+            }
+            Self::MonthlyOnDay(day) if *day <= 28 => format!("FREQ=MONTHLY;BYMONTHDAY={}", (*day).max(1)),
+            Self::MonthlyOnDay(day) => format!("FREQ=MONTHLY;{}", last_day_up_to(*day)),
+            Self::Every { interval, unit: RecurrenceUnit::Months, day } => {
+                let interval = (*interval).max(1);
+                match day.unwrap_or(start.day()) {
+                    day if day <= 28 => format!("FREQ=MONTHLY;INTERVAL={}", interval),
+                    day => format!("FREQ=MONTHLY;INTERVAL={};{}", interval, last_day_up_to(day)),
+                }
+            }
+            Self::Every { interval, unit, .. } => {
+                let freq = match unit {
+                    RecurrenceUnit::Days => "DAILY",
+                    _ => "WEEKLY",
+                };
+                format!("FREQ={};INTERVAL={}", freq, (*interval).max(1))
+            }
+        }
+    }
+}
+
+pub(crate) fn get_new_pastel_colour() -> String {
+    format!{"rgb({}, {}, {})", fastrand::u8(200..), fastrand::u8(200..), fastrand::u8(200..)}
+}
+
+/// how many characters a note's title and body may hold, set per deployment in the server config
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct NoteLimits {
+    pub title: u64,
+    pub body: u64,
+}
+
+impl Default for NoteLimits {
+    fn default() -> Self {
+        Self {
+            title: 100,
+            body: 1000,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum NoteValidationError {
+    TitleTooLong { limit: u64, length: u64 },
+    BodyTooLong { limit: u64, length: u64 },
+}
+
+impl std::fmt::Display for NoteValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TitleTooLong { limit, length } => write!(f, "title is {} characters long, the limit is {}", length, limit),
+            Self::BodyTooLong { limit, length } => write!(f, "body is {} characters long, the limit is {}", length, limit),
+        }
+    }
+}
+
+impl std::error::Error for NoteValidationError {}
+
+/// counts what a reader would call characters (grapheme clusters), so an emoji
+/// or an accented letter made of several code points only counts once
+pub fn count_chars(text: &str) -> u64 {
+    text.graphemes(true).count() as u64
+}
+
+pub fn count_words(text: &str) -> u64 {
+    text.unicode_words().count() as u64
+}
+
+pub(crate) fn truncate_chars(text: String, limit: u64) -> String {
+    match text.grapheme_indices(true).nth(limit as usize) {
+        Some((end, _)) => text[..end].to_owned(),
+        None => text,
+    }
+}
+
+impl NoteLimits {
+    pub fn check(&self, title: &str, body: &str) -> Result<(), NoteValidationError> {
+        self.check_edit(title, body, "", "")
+    }
+
+    /// like `check`, but a title or body that was already over the limit (the limit
+    /// may have been lowered since it was written) only fails if the edit makes it longer
+    pub fn check_edit(&self, title: &str, body: &str, previous_title: &str, previous_body: &str) -> Result<(), NoteValidationError> {
+        let grown_past = |limit: u64, text: &str, previous: &str| {
+            let length = count_chars(text);
+            (length > limit && length > count_chars(previous)).then_some(length)
+        };
+
+        if let Some(length) = grown_past(self.title, title, previous_title) {
+            return Err(NoteValidationError::TitleTooLong { limit: self.title, length });
+        }
+        if let Some(length) = grown_past(self.body, body, previous_body) {
+            return Err(NoteValidationError::BodyTooLong { limit: self.body, length });
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn truncate_title(&self, title: String) -> String {
+        truncate_chars(title, self.title)
+    }
+
+    pub(crate) fn truncate_body(&self, body: String) -> String {
+        truncate_chars(body, self.body)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SerializedNote {
+    pub id: Uuid,
+    pub title: String,
+    pub body: String,
+    pub user: SerializedUser,
+    pub date: TimeDate,
+    pub last_edit: Option<TimeDate>,
+    #[serde(default)]
+    pub access: Vec<NoteAccess>,
+    #[serde(default)]
+    pub share_link: Option<ShareLink>,
+    #[serde(default)]
+    pub comments: Vec<Comment>,
+    #[serde(default)]
+    pub kind: NoteKind,
+    #[serde(default)]
+    pub items: Vec<ChecklistItem>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub due: Option<TimeDate>,
+    #[serde(default)]
+    pub reminder: Option<TimeDate>,
+    #[serde(default)]
+    pub reminder_sent: bool,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default)]
+    pub links: Vec<NoteLink>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default = "get_new_pastel_colour")]
+    pub colour: String,
+    #[serde(default)]
+    pub pinned: bool,
+    /// listed in the owner's public feed, a share link alone doesn't put it there
+    #[serde(default)]
+    pub in_feed: bool,
+}
+
+impl SerializedNote {
+    pub fn new(title: String, body: String, user: SerializedUser) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            title,
+            body,
+            user,
+            date: TimeDate::default(),
+            last_edit: None,
+            access: Vec::new(),
+            share_link: None,
+            comments: Vec::new(),
+            kind: NoteKind::Text,
+            items: Vec::new(),
+            attachments: Vec::new(),
+            due: None,
+            reminder: None,
+            reminder_sent: false,
+            recurrence: None,
+            links: Vec::new(),
+            tags: Vec::new(),
+            colour: get_new_pastel_colour(),
+            pinned: false,
+            in_feed: false,
+        }
+    }
+
+    pub fn access_level(&self, user: Uuid) -> Option<AccessLevel> {
+        if self.user.uuid == user {
+            return Some(AccessLevel::Write);
+        }
+
+        self.access.iter().find(|entry| entry.user.uuid == user).map(|entry| entry.level)
+    }
+
+    /// this note with `edit` applied on behalf of `editor`, or `None` if they can't write to it.
+    /// who owns the note, when it was made, who it's shared with, its share link, its
+    /// comments and whether it's in the feed never come from the client, they each have
+    /// their own server functions
+    #[cfg(feature = "ssr")]
+    pub(crate) fn apply_edit(&self, edit: SerializedNote, editor: Uuid) -> Option<SerializedNote> {
+        if self.access_level(editor) != Some(AccessLevel::Write) {
+            return None;
+        }
+
+        Some(SerializedNote {
+            id: self.id,
+            user: self.user.clone(),
+            date: self.date.clone(),
+            share_link: self.share_link.clone(),
+            comments: self.comments.clone(),
+            access: self.access.clone(),
+            in_feed: self.in_feed,
+            ..edit
+        })
+    }
+
+    /// the note as `viewer` gets to see it. only the owner sees everyone it's shared with
+    /// and its share link, anyone else just sees their own access
+    #[cfg(feature = "ssr")]
+    pub(crate) fn visible_to(mut self, viewer: Uuid) -> Self {
+        if self.user.uuid != viewer {
+            self.access.retain(|entry| entry.user.uuid == viewer);
+            self.share_link = None;
+        }
+        self
+    }
+
+    /// gives `user` `level` access, or changes the access they already have. the owner
+    /// always has full access so they're never added
+    #[cfg(feature = "ssr")]
+    pub(crate) fn share_with(&mut self, user: SerializedUser, level: AccessLevel) {
+        if user.uuid == self.user.uuid {
+            return;
+        }
+
+        match self.access.iter_mut().find(|entry| entry.user.uuid == user.uuid) {
+            Some(entry) => entry.level = level,
+            None => self.access.push(NoteAccess { user, level }),
+        }
+    }
+
+    #[cfg(feature = "ssr")]
+    pub(crate) fn unshare(&mut self, uuid: Uuid) {
+        self.access.retain(|entry| entry.user.uuid != uuid);
+    }
+}
+
+pub const EXPORT_VERSION: u32 = 1;
+
+/// the backup format, bump `EXPORT_VERSION` whenever this stops being backwards compatible
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NotesExport {
+    pub version: u32,
+    pub exported: TimeDate,
+    pub user: SerializedUser,
+    pub notes: Vec<SerializedNote>,
+}
+
+impl NotesExport {
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let export = serde_json::from_str::<NotesExport>(contents).map_err(|e| format!("not a notes export: {}", e))?;
+
+        if export.version == 0 || export.version > EXPORT_VERSION {
+            return Err(format!("unsupported export version {}, expected at most {}", export.version, EXPORT_VERSION));
+        }
+
+        Ok(export)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use chrono_tz::Europe::London;
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn only_the_owner_changes_who_a_note_is_shared_with() {
+        let user = |name: &str| SerializedUser { username: name.to_owned(), creation_date: TimeDate::default(), uuid: Uuid::new_v4() };
+        let (owner, friend) = (user("owner"), user("friend"));
+
+        let mut note = SerializedNote::new("title".to_owned(), String::new(), owner.clone());
+        note.share_with(owner.clone(), AccessLevel::Read);
+        note.share_with(friend.clone(), AccessLevel::Read);
+        note.share_with(friend.clone(), AccessLevel::Write);
+        assert_eq!(note.access, vec![NoteAccess { user: friend.clone(), level: AccessLevel::Write }]);
+
+        //a saved copy can't bring back access that was taken away
+        let stale = note.clone();
+        note.unshare(friend.uuid);
+        assert_eq!(note.access_level(friend.uuid), None);
+        assert!(note.apply_edit(stale, owner.uuid).unwrap().access.is_empty());
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn london(year: i32, month: u32, day: u32, hour: u32) -> TimeDate {
+        TimeDate::from_naive_in(&London, date(year, month, day).and_hms_opt(hour, 0, 0).unwrap())
+    }
+
+    #[test]
+    fn monthly_on_day_clamps_to_short_months() {
+        let recurrence = Recurrence::MonthlyOnDay(31);
+
+        assert_eq!(recurrence.next_date(date(2023, 1, 31)), date(2023, 2, 28));
+        assert_eq!(recurrence.next_date(date(2023, 2, 28)), date(2023, 3, 31));
+        assert_eq!(recurrence.next_date(date(2024, 1, 31)), date(2024, 2, 29));
+        assert_eq!(recurrence.next_date(date(2023, 3, 31)), date(2023, 4, 30));
+    }
+
+    #[test]
+    fn every_month_keeps_its_anchor_day() {
+        let recurrence = Recurrence::Every { interval: 1, unit: RecurrenceUnit::Months, day: Some(31) };
+
+        let feb = recurrence.next_date(date(2023, 1, 31));
+        assert_eq!(feb, date(2023, 2, 28));
+        assert_eq!(recurrence.next_date(feb), date(2023, 3, 31));
+    }
+
+    #[test]
+    fn every_month_without_an_anchor_uses_the_due_day() {
+        let recurrence = Recurrence::Every { interval: 2, unit: RecurrenceUnit::Months, day: None };
+
+        assert_eq!(recurrence.next_date(date(2023, 12, 15)), date(2024, 2, 15));
+    }
+
+    #[test]
+    fn weekly_ignores_days_past_sunday() {
+        assert_eq!(Recurrence::Weekly(vec![7, 9]).next_date(date(2024, 1, 1)), date(2024, 1, 8));
+        assert_eq!(Recurrence::Weekly(vec![9, 2]).next_date(date(2024, 1, 1)), date(2024, 1, 3));
+        assert_eq!(Recurrence::Weekly(vec![7, 9]).to_rrule(date(2024, 1, 1)), "FREQ=WEEKLY");
+        assert_eq!(Recurrence::Weekly(vec![9, 2]).to_rrule(date(2024, 1, 1)), "FREQ=WEEKLY;BYDAY=WE");
+    }
+
+    #[test]
+    fn weekdays_skip_the_weekend() {
+        //2024-01-05 is a friday
+        assert_eq!(Recurrence::Weekdays.next_date(date(2024, 1, 5)), date(2024, 1, 8));
+    }
+
+    #[test]
+    fn keeps_wall_clock_time_across_dst() {
+        //clocks go forward on 2024-03-31 and back on 2024-10-27 in london
+        let spring = Recurrence::Daily.next_after_in(&London, &london(2024, 3, 30, 9), &london(2024, 3, 30, 10));
+        assert_eq!(spring, london(2024, 3, 31, 9));
+        assert_eq!(spring.epoch_time - london(2024, 3, 30, 9).epoch_time, 23 * 60 * 60);
+
+        let autumn = Recurrence::Daily.next_after_in(&London, &london(2024, 10, 26, 9), &london(2024, 10, 26, 10));
+        assert_eq!(autumn.epoch_time - london(2024, 10, 26, 9).epoch_time, 25 * 60 * 60);
+    }
+
+    #[test]
+    fn times_in_a_dst_gap_move_forward_an_hour() {
+        //1am doesn't exist on 2024-03-31 in london
+        let next = Recurrence::Daily.next_after_in(&London, &london(2024, 3, 30, 1), &london(2024, 3, 30, 2));
+        assert_eq!(next, london(2024, 3, 31, 2));
+    }
+
+    #[test]
+    fn characters_are_counted_as_graphemes() {
+        assert_eq!(count_chars("cafe\u{301}"), 4);
+        assert_eq!(count_chars("\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}"), 1);
+        assert_eq!(truncate_chars("cafe\u{301}s".to_owned(), 4), "cafe\u{301}");
+    }
+
+    #[test]
+    fn limits_reject_long_titles_and_bodies() {
+        let limits = NoteLimits { title: 3, body: 5 };
+
+        assert_eq!(limits.check("abc", "abcde"), Ok(()));
+        assert_eq!(limits.check("abcd", ""), Err(NoteValidationError::TitleTooLong { limit: 3, length: 4 }));
+        assert_eq!(limits.check("", "abcdef"), Err(NoteValidationError::BodyTooLong { limit: 5, length: 6 }));
+    }
+
+    #[test]
+    fn text_already_over_the_limit_can_stay_or_shrink() {
+        let limits = NoteLimits { title: 3, body: 5 };
+
+        assert_eq!(limits.check_edit("abcd", "abcdefg", "abcd", "abcdefg"), Ok(()));
+        assert_eq!(limits.check_edit("abcd", "abcdef", "abcd", "abcdefg"), Ok(()));
+        assert_eq!(
+            limits.check_edit("abcd", "abcdefgh", "abcd", "abcdefg"),
+            Err(NoteValidationError::BodyTooLong { limit: 5, length: 8 })
+        );
+    }
+
+    #[test]
+    fn skips_occurrences_in_the_past() {
+        let next = Recurrence::Daily.next_after_in(&London, &london(2024, 1, 1, 9), &london(2024, 1, 10, 12));
+        assert_eq!(next, london(2024, 1, 11, 9));
+    }
+}
@@ -0,0 +1,471 @@
+//! openapi 3 description of the `/v1` api, served at `/v1/openapi.json`.
+//! kept by hand next to the types, so anything added to `SerializedNote` or the
+//! request bodies in `api` needs adding here too. the tests check every schema
+//! against what serde actually writes, so a field that's missing here fails them
+
+use serde_json::{json, Value};
+
+fn nullable(schema: &str) -> Value {
+    json!({ "oneOf": [{ "$ref": format!("#/components/schemas/{}", schema) }, { "type": "null" }] })
+}
+
+fn schema_ref(schema: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", schema) })
+}
+
+fn array_of(schema: &str) -> Value {
+    json!({ "type": "array", "items": schema_ref(schema) })
+}
+
+fn uuid() -> Value {
+    json!({ "type": "string", "format": "uuid" })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+fn error_response(description: &str) -> Value {
+    json_response(description, schema_ref("Error"))
+}
+
+fn schemas() -> Value {
+    json!({
+        "TimeDate": {
+            "type": "object",
+            "description": "a point in time as seconds since the unix epoch",
+            "required": ["epoch_time"],
+            "properties": { "epoch_time": { "type": "integer", "format": "int64" } }
+        },
+        "SerializedUser": {
+            "type": "object",
+            "required": ["username", "creation_date", "uuid"],
+            "properties": {
+                "username": { "type": "string" },
+                "creation_date": schema_ref("TimeDate"),
+                "uuid": uuid()
+            }
+        },
+        "AccessLevel": { "type": "string", "enum": ["Read", "Write"] },
+        "NoteAccess": {
+            "type": "object",
+            "required": ["user", "level"],
+            "properties": { "user": schema_ref("SerializedUser"), "level": schema_ref("AccessLevel") }
+        },
+        "ShareLink": {
+            "type": "object",
+            "required": ["token", "note_id"],
+            "properties": {
+                "token": { "type": "string" },
+                "note_id": uuid(),
+                "expires": nullable("TimeDate")
+            }
+        },
+        "Comment": {
+            "type": "object",
+            "required": ["id", "author", "body", "date"],
+            "properties": {
+                "id": uuid(),
+                "author": schema_ref("SerializedUser"),
+                "body": { "type": "string" },
+                "date": schema_ref("TimeDate"),
+                "last_edit": nullable("TimeDate")
+            }
+        },
+        "NoteKind": { "type": "string", "enum": ["Text", "Checklist"] },
+        "ChecklistItem": {
+            "type": "object",
+            "required": ["id", "text", "done"],
+            "properties": { "id": uuid(), "text": { "type": "string" }, "done": { "type": "boolean" } }
+        },
+        "Attachment": {
+            "type": "object",
+            "required": ["id", "name", "content_type", "size", "blob"],
+            "properties": {
+                "id": uuid(),
+                "name": { "type": "string" },
+                "content_type": { "type": "string" },
+                "size": { "type": "integer", "format": "int64", "minimum": 0 },
+                "blob": { "type": "string", "description": "served from /attachments/{blob}" }
+            }
+        },
+        "RecurrenceUnit": { "type": "string", "enum": ["Days", "Weeks", "Months"] },
+        "Recurrence": {
+            "description": "how a due date repeats, weekdays are counted from monday = 0",
+            "oneOf": [
+                { "type": "string", "enum": ["Daily", "Weekdays"] },
+                {
+                    "type": "object",
+                    "required": ["Weekly"],
+                    "properties": { "Weekly": { "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 6 } } }
+                },
+                {
+                    "type": "object",
+                    "required": ["MonthlyOnDay"],
+                    "properties": { "MonthlyOnDay": { "type": "integer", "minimum": 1, "maximum": 31 } }
+                },
+                {
+                    "type": "object",
+                    "required": ["Every"],
+                    "properties": {
+                        "Every": {
+                            "type": "object",
+                            "required": ["interval", "unit"],
+                            "properties": {
+                                "interval": { "type": "integer", "minimum": 1 },
+                                "unit": schema_ref("RecurrenceUnit"),
+                                "day": {
+                                    "description": "the day of the month monthly repeats aim for",
+                                    "type": ["integer", "null"],
+                                    "minimum": 1,
+                                    "maximum": 31
+                                }
+                            }
+                        }
+                    }
+                }
+            ]
+        },
+        "NoteLink": {
+            "type": "object",
+            "required": ["label", "target"],
+            "properties": { "label": { "type": "string" }, "target": uuid() }
+        },
+        "SerializedNote": {
+            "type": "object",
+            "required": ["id", "title", "body", "user", "date"],
+            "properties": {
+                "id": uuid(),
+                "title": { "type": "string" },
+                "body": { "type": "string", "description": "commonmark, `[[title]]` links to other notes" },
+                "user": schema_ref("SerializedUser"),
+                "date": schema_ref("TimeDate"),
+                "last_edit": nullable("TimeDate"),
+                "access": {
+                    "type": "array",
+                    "items": schema_ref("NoteAccess"),
+                    "description": "everyone the note is shared with, other callers only see their own entry"
+                },
+                "share_link": {
+                    "oneOf": [schema_ref("ShareLink"), { "type": "null" }],
+                    "description": "only ever set for the note's owner"
+                },
+                "comments": array_of("Comment"),
+                "kind": schema_ref("NoteKind"),
+                "items": array_of("ChecklistItem"),
+                "attachments": array_of("Attachment"),
+                "due": nullable("TimeDate"),
+                "reminder": nullable("TimeDate"),
+                "reminder_sent": { "type": "boolean" },
+                "recurrence": nullable("Recurrence"),
+                "links": array_of("NoteLink"),
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "colour": { "type": "string", "description": "any css colour" },
+                "pinned": { "type": "boolean" },
+                "in_feed": { "type": "boolean", "description": "listed in the owner's public feed" }
+            }
+        },
+        "CreateNote": {
+            "type": "object",
+            "required": ["title"],
+            "properties": {
+                "title": { "type": "string" },
+                "body": { "type": "string" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "pinned": { "type": "boolean" },
+                "due": nullable("TimeDate")
+            }
+        },
+        "PatchNote": {
+            "type": "object",
+            "description": "missing fields are left alone, `due: null` clears the due date",
+            "properties": {
+                "title": { "type": "string" },
+                "body": { "type": "string" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "pinned": { "type": "boolean" },
+                "due": nullable("TimeDate")
+            }
+        },
+        "Error": {
+            "type": "object",
+            "required": ["error"],
+            "properties": { "error": { "type": "string" } }
+        }
+    })
+}
+
+fn id_parameter() -> Value {
+    json!({ "name": "id", "in": "path", "required": true, "schema": uuid() })
+}
+
+fn paths() -> Value {
+    json!({
+        "/v1/openapi.json": {
+            "get": {
+                "operationId": "openapi",
+                "summary": "this document",
+                "security": [],
+                "responses": {
+                    "200": json_response("the openapi description", json!({ "type": "object" }))
+                }
+            }
+        },
+        "/v1/me": {
+            "get": {
+                "operationId": "me",
+                "summary": "the user the token belongs to",
+                "responses": {
+                    "200": json_response("the caller", schema_ref("SerializedUser")),
+                    "401": error_response("missing or invalid token")
+                }
+            }
+        },
+        "/v1/notes": {
+            "get": {
+                "operationId": "listNotes",
+                "summary": "list every note the caller owns or has been shared",
+                "parameters": [
+                    { "name": "pinned", "in": "query", "schema": { "type": "boolean" } },
+                    { "name": "tag", "in": "query", "schema": { "type": "string" } },
+                    { "name": "q", "in": "query", "description": "case insensitive search of titles and bodies", "schema": { "type": "string" } },
+                    { "name": "overdue", "in": "query", "schema": { "type": "boolean" } }
+                ],
+                "responses": {
+                    "200": json_response("matching notes, newest first", array_of("SerializedNote")),
+                    "400": error_response("bad query parameters"),
+                    "401": error_response("missing or invalid token")
+                }
+            },
+            "post": {
+                "operationId": "createNote",
+                "summary": "create a note owned by the caller",
+                "requestBody": { "required": true, "content": { "application/json": { "schema": schema_ref("CreateNote") } } },
+                "responses": {
+                    "201": json_response("the created note", schema_ref("SerializedNote")),
+                    "400": error_response("malformed body"),
+                    "401": error_response("missing or invalid token"),
+                    "422": error_response("the title or body is over the server's character limit")
+                }
+            }
+        },
+        "/v1/notes/{id}": {
+            "parameters": [id_parameter()],
+            "get": {
+                "operationId": "getNote",
+                "responses": {
+                    "200": json_response("the note", schema_ref("SerializedNote")),
+                    "401": error_response("missing or invalid token"),
+                    "404": error_response("no such note, or the caller can't see it")
+                }
+            },
+            "patch": {
+                "operationId": "patchNote",
+                "summary": "update a note, needs write access",
+                "requestBody": { "required": true, "content": { "application/json": { "schema": schema_ref("PatchNote") } } },
+                "responses": {
+                    "200": json_response("the updated note", schema_ref("SerializedNote")),
+                    "400": error_response("malformed body"),
+                    "401": error_response("missing or invalid token"),
+                    "403": error_response("the caller only has read access"),
+                    "404": error_response("no such note, or the caller can't see it"),
+                    "422": error_response("the title or body is over the server's character limit")
+                }
+            },
+            "delete": {
+                "operationId": "deleteNote",
+                "summary": "permanently delete a note, owner only",
+                "responses": {
+                    "204": { "description": "deleted" },
+                    "401": error_response("missing or invalid token"),
+                    "403": error_response("the caller doesn't own the note"),
+                    "404": error_response("no such note, or the caller can't see it")
+                }
+            }
+        }
+    })
+}
+
+pub fn document() -> Value {
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "notes api",
+            "version": "1",
+            "description": "create personal access tokens from the settings page and send them as a bearer token"
+        },
+        "servers": [{ "url": "/" }],
+        "security": [{ "bearerAuth": [] }],
+        "paths": paths(),
+        "components": {
+            "securitySchemes": { "bearerAuth": { "type": "http", "scheme": "bearer" } },
+            "schemas": schemas()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{CreateNote, ErrorBody, PatchNote};
+    use crate::model::{
+        AccessLevel, Attachment, ChecklistItem, Comment, NoteAccess, NoteKind, NoteLink, Recurrence, RecurrenceUnit, SerializedNote,
+        SerializedUser, ShareLink, TimeDate,
+    };
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    fn type_matches(name: &str, value: &Value) -> bool {
+        match name {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            _ => false,
+        }
+    }
+
+    /// checks `value` against `schema`, following refs into `schemas`. objects have to
+    /// list exactly the fields serde wrote, so the samples below fill in every field.
+    /// only understands the parts of json schema this file uses
+    fn check(schemas: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            return check(schemas, &schemas[name], value, at);
+        }
+
+        if let Some(options) = schema["oneOf"].as_array() {
+            return match options.iter().any(|option| check(schemas, option, value, at).is_ok()) {
+                true => Ok(()),
+                false => Err(format!("{}: {} matches none of the options", at, value)),
+            };
+        }
+
+        if let Some(allowed) = schema["enum"].as_array() {
+            if !allowed.contains(value) {
+                return Err(format!("{}: {} isn't one of {:?}", at, value, allowed));
+            }
+        }
+
+        let types = match &schema["type"] {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|name| type_matches(name, value)) {
+            return Err(format!("{}: {} isn't {:?}", at, value, types));
+        }
+
+        if let (Some(fields), Some(properties)) = (value.as_object(), schema["properties"].as_object()) {
+            if let Some(field) = fields.keys().find(|field| !properties.contains_key(*field)) {
+                return Err(format!("{}: `{}` is written by serde but missing from the schema", at, field));
+            }
+            if let Some(property) = properties.keys().find(|property| !fields.contains_key(*property)) {
+                return Err(format!("{}: `{}` is in the schema but serde doesn't write it", at, property));
+            }
+            for (field, field_value) in fields {
+                check(schemas, &properties[field], field_value, &format!("{}.{}", at, field))?;
+            }
+        }
+
+        if let (Some(items), Some(item_schema)) = (value.as_array(), schema.get("items")) {
+            for (i, item) in items.iter().enumerate() {
+                check(schemas, item_schema, item, &format!("{}[{}]", at, i))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn assert_matches(name: &str, sample: impl Serialize) {
+        let schemas = schemas();
+        assert!(schemas.get(name).is_some(), "no schema called {}", name);
+        let value = serde_json::to_value(sample).unwrap();
+        if let Err(e) = check(&schemas, &schemas[name], &value, name) {
+            panic!("{}", e);
+        }
+    }
+
+    fn user() -> SerializedUser {
+        SerializedUser {
+            username: "ann".to_owned(),
+            creation_date: TimeDate::default(),
+            uuid: Uuid::new_v4(),
+        }
+    }
+
+    /// every optional part filled in so nothing in the schema goes unchecked
+    fn full_note() -> SerializedNote {
+        let mut note = SerializedNote::new("title".to_owned(), "body".to_owned(), user());
+        note.last_edit = Some(TimeDate::default());
+        note.access = vec![NoteAccess { user: user(), level: AccessLevel::Write }];
+        note.share_link = Some(ShareLink::new(note.id, Some(TimeDate::days_from_now(1))));
+        note.comments = vec![Comment { id: Uuid::new_v4(), author: user(), body: "hi".to_owned(), date: TimeDate::default(), last_edit: Some(TimeDate::default()) }];
+        note.kind = NoteKind::Checklist;
+        note.items = vec![ChecklistItem::new("milk".to_owned())];
+        note.attachments = vec![Attachment {
+            id: Uuid::new_v4(),
+            name: "a.png".to_owned(),
+            content_type: "image/png".to_owned(),
+            size: 10,
+            blob: "abc".to_owned(),
+        }];
+        note.due = Some(TimeDate::default());
+        note.reminder = Some(TimeDate::default());
+        note.recurrence = Some(Recurrence::Daily);
+        note.links = vec![NoteLink { label: "other".to_owned(), target: Uuid::new_v4() }];
+        note.tags = vec!["work".to_owned()];
+        note
+    }
+
+    #[test]
+    fn notes_and_users_match_their_schemas() {
+        assert_matches("SerializedUser", user());
+        assert_matches("SerializedNote", full_note());
+        assert_matches("Error", ErrorBody { error: "nope".to_owned() });
+    }
+
+    #[test]
+    fn every_recurrence_matches_its_schema() {
+        for recurrence in [
+            Recurrence::Daily,
+            Recurrence::Weekdays,
+            Recurrence::Weekly(vec![0, 6]),
+            Recurrence::MonthlyOnDay(31),
+            Recurrence::Every { interval: 2, unit: RecurrenceUnit::Months, day: Some(31) },
+            Recurrence::Every { interval: 1, unit: RecurrenceUnit::Days, day: None },
+        ] {
+            assert_matches("Recurrence", recurrence);
+        }
+    }
+
+    #[test]
+    fn request_bodies_match_their_schemas() {
+        assert_matches("CreateNote", CreateNote {
+            title: "title".to_owned(),
+            body: "body".to_owned(),
+            tags: vec!["work".to_owned()],
+            pinned: true,
+            due: Some(TimeDate::default()),
+        });
+        assert_matches("PatchNote", PatchNote {
+            title: Some("title".to_owned()),
+            body: Some("body".to_owned()),
+            tags: Some(vec!["work".to_owned()]),
+            pinned: Some(false),
+            due: Some(Some(TimeDate::default())),
+        });
+    }
+
+    #[test]
+    fn the_spec_itself_needs_no_token() {
+        let document = document();
+        assert_eq!(document["paths"]["/v1/openapi.json"]["get"]["security"], json!([]));
+        assert_eq!(document["security"], json!([{ "bearerAuth": [] }]));
+    }
+}
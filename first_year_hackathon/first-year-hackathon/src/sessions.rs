@@ -0,0 +1,130 @@
+//! cookie sessions for the browser app
+//!
+//! signing in takes a username and password, the password is kept as an argon2
+//! hash. it hands the browser a random token in an http only cookie, server
+//! functions find the caller from it rather than trusting a user id sent along
+//! with the request. like access tokens, only the sha256 of the token is stored.
+//! if the server sets `session_lifetime_days`, sessions older than that expire.
+
+use crate::api::hash_token;
+use crate::model::{SerializedUser, TimeDate};
+use crate::store;
+use actix_web::{cookie::{Cookie, SameSite}, http::header, HttpRequest};
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use leptos::{use_context, Scope, ServerFnError};
+use leptos_actix::ResponseOptions;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const COOKIE_NAME: &str = "notes_session";
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub user: Uuid,
+    pub created: TimeDate,
+}
+
+impl Session {
+    fn is_expired(&self) -> bool {
+        crate::config::get()
+            .session_lifetime()
+            .is_some_and(|lifetime| self.created.offset_by(lifetime).has_passed())
+    }
+}
+
+/// the user behind a request's session cookie, if it is still valid. for plain
+/// actix handlers, server functions should use `current_user`
+pub fn request_user(req: &HttpRequest) -> Option<SerializedUser> {
+    let cookie = req.cookie(COOKIE_NAME)?;
+    let hash = hash_token(cookie.value());
+
+    store::read(|store| {
+        let session = store.sessions.get(&hash).filter(|session| !session.is_expired())?;
+        store.users.get(&session.user).cloned()
+    })
+}
+
+fn session_user(cx: Scope) -> Option<SerializedUser> {
+    request_user(&use_context::<HttpRequest>(cx)?)
+}
+
+/// the signed in caller of a server function
+pub fn current_user(cx: Scope) -> Result<SerializedUser, ServerFnError> {
+    session_user(cx).ok_or_else(|| ServerFnError::ServerError("not signed in".to_owned()))
+}
+
+pub fn hash_password(password: &str) -> Result<String, ServerFnError> {
+    //a v4 uuid is 122 random bits, plenty for a salt
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes()).map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+/// the user `username` if `password` is theirs
+pub fn check_password(username: &str, password: &str) -> Option<SerializedUser> {
+    store::read(|store| {
+        let user = store.user_by_name(username)?;
+        let hash = store.passwords.get(&user.uuid)?;
+        verify_password(hash, password).then(|| user.clone())
+    })
+}
+
+/// signs `user` in, keeping the current session if it already belongs to them
+pub fn start(cx: Scope, user: Uuid) -> Result<(), ServerFnError> {
+    if session_user(cx).is_some_and(|current| current.uuid == user) {
+        return Ok(());
+    }
+
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let session = Session {
+        user,
+        created: TimeDate::default(),
+    };
+
+    store::write(|store| {
+        store.sessions.retain(|_, session| !session.is_expired());
+        store.sessions.insert(hash_token(&token), session);
+    })
+    .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+
+    let mut cookie = Cookie::build(COOKIE_NAME, token)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .finish();
+    if let Some(lifetime) = crate::config::get().session_lifetime() {
+        cookie.set_max_age(actix_web::cookie::time::Duration::seconds(lifetime));
+    }
+
+    let value = header::HeaderValue::from_str(&cookie.to_string())
+        .map_err(|e| ServerFnError::ServerError(e.to_string()))?;
+    if let Some(res) = use_context::<ResponseOptions>(cx) {
+        res.append_header(header::SET_COOKIE, value);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwords_only_match_themselves() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(verify_password(&hash, "correct horse"));
+        assert!(!verify_password(&hash, "correct horse "));
+        assert!(!verify_password("not a hash", "correct horse"));
+        assert_ne!(hash, hash_password("correct horse").unwrap());
+    }
+}
@@ -0,0 +1,163 @@
+//! server side persistence for notes, everything lives in memory and is
+//! flushed to a json file after each write
+
+use crate::app::{AccessToken, NoteTemplate, User, Webhook};
+use crate::model::{SerializedNote, SerializedUser, ShareLink};
+use crate::sessions::Session;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{OnceLock, RwLock},
+};
+use uuid::Uuid;
+
+static STORE: OnceLock<RwLock<NoteStore>> = OnceLock::new();
+
+/// why a new account couldn't be made
+#[derive(Debug, PartialEq, Eq)]
+pub enum RegisterError {
+    /// the server was configured with `registration_open = false`
+    Closed,
+    Taken,
+}
+
+impl std::fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Closed => write!(f, "registration is closed on this server"),
+            Self::Taken => write!(f, "that username is taken"),
+        }
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct NoteStore {
+    pub notes: HashMap<Uuid, SerializedNote>,
+    pub share_links: HashMap<String, ShareLink>,
+    #[serde(default)]
+    pub templates: HashMap<Uuid, NoteTemplate>,
+    #[serde(default)]
+    pub users: HashMap<Uuid, SerializedUser>,
+    /// keyed by the sha256 of the token, the token itself is never stored
+    #[serde(default)]
+    pub access_tokens: HashMap<String, AccessToken>,
+    #[serde(default)]
+    pub webhooks: HashMap<Uuid, Webhook>,
+    /// sha256 of a calendar link token to the user it belongs to
+    #[serde(default)]
+    pub calendar_tokens: HashMap<String, Uuid>,
+    /// sha256 of a session cookie to the session it belongs to
+    #[serde(default)]
+    pub sessions: HashMap<String, Session>,
+    /// argon2 hash of each user's password, users from before passwords can't sign in
+    #[serde(default)]
+    pub passwords: HashMap<Uuid, String>,
+    /// who uploaded each blob, so they can see it before the note pointing at it is saved
+    #[serde(default)]
+    pub uploads: HashMap<String, HashSet<Uuid>>,
+    /// notes with unfinished edits, as they were before the first draft save. only
+    /// needed for webhooks, so it isn't worth persisting
+    #[serde(skip)]
+    pub drafts: HashMap<Uuid, Option<SerializedNote>>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl NoteStore {
+    fn load(path: &Path) -> io::Result<Self> {
+        let mut store = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str::<NoteStore>(&contents)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => NoteStore::default(),
+            Err(e) => return Err(e),
+        };
+
+        store.path = Some(path.to_owned());
+        Ok(store)
+    }
+
+    fn persist(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let contents = serde_json::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        //write then rename so a crash never leaves a half written file behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(tmp, path)
+    }
+
+    /// looks up the note behind a share token, ignoring expired links
+    pub fn shared_note(&self, token: &str) -> Option<&SerializedNote> {
+        self.share_links
+            .get(token)
+            .filter(|link| !link.is_expired())
+            .and_then(|link| self.notes.get(&link.note_id))
+    }
+
+    pub fn user_by_name(&self, username: &str) -> Option<&SerializedUser> {
+        self.users.values().find(|user| user.username == username)
+    }
+
+    /// signs `username` up with an already hashed password. every account is made
+    /// here so nothing can get around `registration_open`
+    pub fn register(&mut self, username: &str, password_hash: String) -> Result<SerializedUser, RegisterError> {
+        if !crate::config::get().registration_open {
+            return Err(RegisterError::Closed);
+        }
+        if self.user_by_name(username).is_some() {
+            return Err(RegisterError::Taken);
+        }
+
+        let user = SerializedUser::from(&User::new_user(username.to_owned()));
+        self.users.insert(user.uuid, user.clone());
+        self.passwords.insert(user.uuid, password_hash);
+        Ok(user)
+    }
+
+    /// every attachment blob that is still attached to at least one note
+    pub fn referenced_blobs(&self) -> HashSet<&str> {
+        self.notes
+            .values()
+            .flat_map(|note| note.attachments.iter().map(|attachment| attachment.blob.as_str()))
+            .collect()
+    }
+
+    /// blobs are only served to whoever uploaded them and people who can see a note they're on
+    pub fn can_view_blob(&self, blob: &str, viewer: Uuid) -> bool {
+        self.uploads.get(blob).is_some_and(|uploaders| uploaders.contains(&viewer))
+            || self.notes.values().any(|note| {
+                note.access_level(viewer).is_some() && note.attachments.iter().any(|attachment| attachment.blob == blob)
+            })
+    }
+}
+
+/// loads the store from `path`, must be called once before any server function runs
+pub fn init(path: &Path) -> io::Result<()> {
+    let store = NoteStore::load(path)?;
+    STORE
+        .set(RwLock::new(store))
+        .map_err(|_| io::Error::new(io::ErrorKind::AlreadyExists, "note store already initialised"))
+}
+
+fn store() -> &'static RwLock<NoteStore> {
+    STORE.get_or_init(|| RwLock::new(NoteStore::default()))
+}
+
+pub fn read<T>(f: impl FnOnce(&NoteStore) -> T) -> T {
+    f(&store().read().unwrap_or_else(|e| e.into_inner()))
+}
+
+pub fn write<T>(f: impl FnOnce(&mut NoteStore) -> T) -> io::Result<T> {
+    let mut store = store().write().unwrap_or_else(|e| e.into_inner());
+    let out = f(&mut store);
+    store.persist()?;
+    Ok(out)
+}
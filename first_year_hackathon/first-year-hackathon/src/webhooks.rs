@@ -0,0 +1,504 @@
+//! outgoing webhooks, every note event a user subscribed to is posted as json
+//! to their url, signed with `X-Notes-Signature: sha256=<hmac of the body>`
+//! using the webhook's secret. failed deliveries are retried with exponential
+//! backoff and every attempt is kept in the webhook's delivery log
+//!
+//! urls have to resolve to public addresses, both when the webhook is made and
+//! before each delivery, so a webhook can't be pointed at the server's own
+//! network. the payload leaves out the note's share link and access list.
+
+use crate::app::{NoteEvent, Webhook, WebhookDelivery};
+use crate::model::{SerializedNote, TimeDate};
+use crate::store;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+use url::Url;
+use uuid::Uuid;
+
+const MAX_ATTEMPTS: u32 = 5;
+#[cfg(not(test))]
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+#[cfg(test)]
+const FIRST_RETRY: Duration = Duration::from_millis(10);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// how many deliveries each webhook remembers
+const DELIVERY_LOG_LENGTH: usize = 50;
+/// note fields that would hand out access to the note if the payload leaked
+const PRIVATE_FIELDS: &[&str] = &["share_link", "access"];
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    delivery: Uuid,
+    event: NoteEvent,
+    timestamp: TimeDate,
+    note: &'a serde_json::Value,
+}
+
+/// loopback, private, link-local and other addresses that don't belong to the public internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                //carrier grade nat, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    //unique local, fc00::/7
+                    || (first & 0xfe00) == 0xfc00
+                    //link local, fe80::/10
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// checks the url is http(s) and that every address its host resolves to is public,
+/// returning the address to connect to
+pub async fn resolve_target(url: &str) -> Result<SocketAddr, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("invalid url: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("webhooks must use http or https".to_owned());
+    }
+
+    let host = parsed.host_str().ok_or("webhook urls need a host")?.to_owned();
+    let port = parsed.port_or_known_default().ok_or("webhook urls need a port")?;
+
+    //ipv6 hosts come back from the url bracketed
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_owned();
+    let addrs = actix_web::rt::task::spawn_blocking(move || (host.as_str(), port).to_socket_addrs().map(Vec::from_iter))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("couldn't resolve the webhook host: {}", e))?;
+
+    match addrs.first() {
+        None => Err("the webhook host doesn't resolve to anything".to_owned()),
+        Some(_) if addrs.iter().any(|addr| !is_public(addr.ip())) => Err("webhooks can't point at private or local addresses".to_owned()),
+        Some(addr) => Ok(*addr),
+    }
+}
+
+/// a client that only ever connects to `addr` for the url's host, so dns can't
+/// change its answer between the check and the request
+fn pinned_client(url: &str, addr: SocketAddr) -> reqwest::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder().timeout(DELIVERY_TIMEOUT).redirect(reqwest::redirect::Policy::none());
+    if let Some(host) = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_owned)) {
+        builder = builder.resolve(&host, addr);
+    }
+    builder.build()
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// works out what happened to a note between two saves
+pub fn events_between(previous: Option<&SerializedNote>, current: &SerializedNote) -> Vec<NoteEvent> {
+    let Some(previous) = previous else {
+        return vec![NoteEvent::Created];
+    };
+
+    let mut events = Vec::new();
+
+    if previous.title != current.title || previous.body != current.body || previous.items != current.items || previous.tags != current.tags {
+        events.push(NoteEvent::Edited);
+    }
+
+    if !previous.pinned && current.pinned {
+        events.push(NoteEvent::Pinned);
+    }
+
+    let seen = previous.comments.iter().map(|comment| comment.id).collect::<HashSet<_>>();
+    if current.comments.iter().any(|comment| !seen.contains(&comment.id)) {
+        events.push(NoteEvent::Commented);
+    }
+
+    events
+}
+
+/// the events a save of `note` over `previous` should send. drafts send nothing but
+/// remember how the note was before the first of them in `drafts`, so the save that
+/// ends the edit reports everything since then and a cancelled edit reports nothing
+pub fn events_for_save(drafts: &mut HashMap<Uuid, Option<SerializedNote>>, previous: Option<SerializedNote>, note: &SerializedNote, draft: bool) -> Vec<NoteEvent> {
+    if draft {
+        drafts.entry(note.id).or_insert(previous);
+        return Vec::new();
+    }
+
+    let before = drafts.remove(&note.id).unwrap_or(previous);
+    events_between(before.as_ref(), note)
+}
+
+/// sends each event to every webhook the note's owner has subscribed to it,
+/// deliveries happen in the background so callers never wait on them
+pub fn dispatch(note: &SerializedNote, events: &[NoteEvent]) {
+    if events.is_empty() {
+        return;
+    }
+
+    let mut public_note = match serde_json::to_value(note) {
+        Ok(public_note) => public_note,
+        Err(e) => {
+            leptos::error!("failed to serialise webhook payload: {}", e);
+            return;
+        }
+    };
+    if let Some(fields) = public_note.as_object_mut() {
+        for field in PRIVATE_FIELDS {
+            fields.remove(*field);
+        }
+    }
+
+    let hooks = store::read(|store| {
+        store
+            .webhooks
+            .values()
+            .filter(|hook| hook.user == note.user.uuid)
+            .cloned()
+            .collect::<Vec<_>>()
+    });
+
+    for event in events {
+        for hook in hooks.iter().filter(|hook| hook.events.contains(event)) {
+            let payload = Payload {
+                delivery: Uuid::new_v4(),
+                event: *event,
+                timestamp: TimeDate::default(),
+                note: &public_note,
+            };
+
+            let body = match serde_json::to_vec(&payload) {
+                Ok(body) => body,
+                Err(e) => {
+                    leptos::error!("failed to serialise webhook payload: {}", e);
+                    continue;
+                }
+            };
+
+            let hook = hook.clone();
+            let delivery = payload.delivery;
+            let event = *event;
+            actix_web::rt::spawn(async move {
+                let client = match resolve_target(&hook.url).await {
+                    Ok(addr) => pinned_client(&hook.url, addr).map_err(|e| e.to_string()),
+                    Err(e) => Err(e),
+                };
+
+                match client {
+                    Ok(client) => deliver(&client, hook, delivery, event, body).await,
+                    Err(e) => record(hook.id, WebhookDelivery {
+                        id: delivery,
+                        event,
+                        attempt: 1,
+                        attempted: TimeDate::default(),
+                        status: None,
+                        error: Some(e),
+                        success: false,
+                    }),
+                }
+            });
+        }
+    }
+}
+
+async fn deliver(client: &reqwest::Client, hook: Webhook, delivery: Uuid, event: NoteEvent, body: Vec<u8>) {
+    let signature = sign(&hook.secret, &body);
+    let mut backoff = FIRST_RETRY;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let result = client
+            .post(&hook.url)
+            .header("Content-Type", "application/json")
+            .header("User-Agent", "notes-webhooks")
+            .header("X-Notes-Event", event.name())
+            .header("X-Notes-Delivery", delivery.to_string())
+            .header("X-Notes-Signature", &signature)
+            .body(body.clone())
+            .send()
+            .await;
+
+        let (status, error) = match result {
+            Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+            Ok(response) => (Some(response.status().as_u16()), Some(format!("endpoint responded {}", response.status()))),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let success = error.is_none();
+        record(hook.id, WebhookDelivery {
+            id: delivery,
+            event,
+            attempt,
+            attempted: TimeDate::default(),
+            status,
+            error,
+            success,
+        });
+
+        if success {
+            return;
+        }
+
+        if attempt < MAX_ATTEMPTS {
+            actix_web::rt::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
+
+fn record(hook: Uuid, delivery: WebhookDelivery) {
+    let result = store::write(|store| {
+        if let Some(hook) = store.webhooks.get_mut(&hook) {
+            hook.deliveries.insert(0, delivery);
+            hook.deliveries.truncate(DELIVERY_LOG_LENGTH);
+        }
+    });
+
+    if let Err(e) = result {
+        leptos::error!("failed to record webhook delivery: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Comment, SerializedUser};
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    fn user() -> SerializedUser {
+        SerializedUser {
+            username: "will".to_owned(),
+            creation_date: TimeDate::default(),
+            uuid: Uuid::new_v4(),
+        }
+    }
+
+    fn note() -> SerializedNote {
+        SerializedNote::new("title".to_owned(), "body".to_owned(), user())
+    }
+
+    #[test]
+    fn drafts_only_report_once_the_edit_ends() {
+        let mut drafts = HashMap::new();
+        let original = note();
+
+        let mut draft = original.clone();
+        draft.title = "typing".to_owned();
+        assert!(events_for_save(&mut drafts, Some(original.clone()), &draft, true).is_empty());
+
+        let mut saved = draft.clone();
+        saved.title = "typed".to_owned();
+        assert!(events_for_save(&mut drafts, Some(draft.clone()), &saved, true).is_empty());
+        assert_eq!(events_for_save(&mut drafts, Some(saved.clone()), &saved, false), vec![NoteEvent::Edited]);
+        assert!(drafts.is_empty());
+    }
+
+    #[test]
+    fn cancelled_drafts_report_nothing() {
+        let mut drafts = HashMap::new();
+        let original = note();
+
+        let mut draft = original.clone();
+        draft.body = "typing".to_owned();
+        assert!(events_for_save(&mut drafts, Some(original.clone()), &draft, true).is_empty());
+        assert!(events_for_save(&mut drafts, Some(draft), &original, false).is_empty());
+    }
+
+    #[test]
+    fn notes_made_as_drafts_are_created_when_saved() {
+        let mut drafts = HashMap::new();
+        let new = note();
+
+        assert!(events_for_save(&mut drafts, None, &new, true).is_empty());
+        assert_eq!(events_for_save(&mut drafts, Some(new.clone()), &new, false), vec![NoteEvent::Created]);
+    }
+
+    fn hook(url: String) -> Webhook {
+        Webhook {
+            id: Uuid::new_v4(),
+            user: Uuid::new_v4(),
+            url,
+            secret: "whsec_test".to_owned(),
+            events: NoteEvent::ALL.to_vec(),
+            created: TimeDate::default(),
+            deliveries: Vec::new(),
+        }
+    }
+
+    /// a one request at a time http server answering every request with `statuses` in order,
+    /// sends each raw request back down the channel
+    fn stand_in(statuses: Vec<u16>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                loop {
+                    let read = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..read]);
+
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text[..end]
+                            .lines()
+                            .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|len| len.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + length {
+                            break;
+                        }
+                    }
+                }
+
+                //hand the request over before answering, so it's there by the time deliver returns
+                let _ = tx.send(String::from_utf8_lossy(&request).into_owned());
+                let _ = write!(stream, "HTTP/1.1 {} stand-in\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+            }
+        });
+
+        (url, rx)
+    }
+
+    #[test]
+    fn sign_matches_a_known_hmac() {
+        //from rfc 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn sign_depends_on_the_secret() {
+        assert_ne!(sign("one", b"body"), sign("two", b"body"));
+    }
+
+    #[test]
+    fn new_notes_are_created() {
+        assert_eq!(events_between(None, &note()), vec![NoteEvent::Created]);
+    }
+
+    #[test]
+    fn unchanged_notes_have_no_events() {
+        let note = note();
+        assert!(events_between(Some(&note), &note.clone()).is_empty());
+    }
+
+    #[test]
+    fn edits_pins_and_comments_are_reported() {
+        let previous = note();
+        let mut current = previous.clone();
+        current.body = "new body".to_owned();
+        current.pinned = true;
+        current.comments.push(Comment {
+            id: Uuid::new_v4(),
+            author: user(),
+            body: "hi".to_owned(),
+            date: TimeDate::default(),
+            last_edit: None,
+        });
+
+        assert_eq!(
+            events_between(Some(&previous), &current),
+            vec![NoteEvent::Edited, NoteEvent::Pinned, NoteEvent::Commented]
+        );
+    }
+
+    #[test]
+    fn unpinning_is_not_an_event() {
+        let mut previous = note();
+        previous.pinned = true;
+        let mut current = previous.clone();
+        current.pinned = false;
+
+        assert!(events_between(Some(&previous), &current).is_empty());
+    }
+
+    #[test]
+    fn local_and_private_addresses_are_not_public() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0", "100.64.0.1", "::1", "fe80::1", "fd00::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public(ip.parse().unwrap()), "{} should not be public", ip);
+        }
+
+        for ip in ["93.184.216.34", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[actix_web::test]
+    async fn targets_must_be_public_http() {
+        assert!(resolve_target("ftp://93.184.216.34/hook").await.is_err());
+        assert!(resolve_target("http://127.0.0.1:8080/hook").await.is_err());
+        assert!(resolve_target("http://[::1]/hook").await.is_err());
+        assert!(resolve_target("http://169.254.169.254/latest/meta-data").await.is_err());
+        assert_eq!(resolve_target("https://93.184.216.34/hook").await, Ok("93.184.216.34:443".parse().unwrap()));
+    }
+
+    #[actix_web::test]
+    async fn deliver_posts_a_signed_body() {
+        let (url, requests) = stand_in(vec![200]);
+        let hook = hook(url);
+        store::write(|store| store.webhooks.insert(hook.id, hook.clone())).unwrap();
+
+        let body = br#"{"event":"created"}"#.to_vec();
+        deliver(&reqwest::Client::new(), hook.clone(), Uuid::new_v4(), NoteEvent::Created, body.clone()).await;
+
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /hook "));
+        assert!(request.contains("x-notes-event: created"));
+        assert!(request.contains(&format!("x-notes-signature: {}", sign(&hook.secret, &body))));
+        assert!(request.ends_with(r#"{"event":"created"}"#));
+
+        let deliveries = store::read(|store| store.webhooks[&hook.id].deliveries.clone());
+        assert_eq!(deliveries.len(), 1);
+        assert!(deliveries[0].success);
+        assert_eq!(deliveries[0].status, Some(200));
+    }
+
+    #[actix_web::test]
+    async fn deliver_retries_until_it_succeeds() {
+        let (url, requests) = stand_in(vec![500, 503, 200]);
+        let hook = hook(url);
+        store::write(|store| store.webhooks.insert(hook.id, hook.clone())).unwrap();
+
+        deliver(&reqwest::Client::new(), hook.clone(), Uuid::new_v4(), NoteEvent::Edited, b"{}".to_vec()).await;
+        assert_eq!(requests.try_iter().count(), 3);
+
+        //newest first
+        let deliveries = store::read(|store| store.webhooks[&hook.id].deliveries.clone());
+        assert_eq!(deliveries.iter().map(|delivery| (delivery.attempt, delivery.status, delivery.success)).collect::<Vec<_>>(), vec![
+            (3, Some(200), true),
+            (2, Some(503), false),
+            (1, Some(500), false),
+        ]);
+    }
+}